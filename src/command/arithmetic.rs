@@ -0,0 +1,521 @@
+use std::fmt::Display;
+
+use crate::data::Input;
+
+// A variable may hold an expression naming another one, but not forever.
+const MAX_DEPTH: usize = 64;
+
+// Longer operators first, so `<<=` is not read as `<` and `<=`.
+const OPERATORS: [&str; 39] = [
+    "<<=", ">>=",
+    "**", "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "++", "--",
+    "+=", "-=", "*=", "/=", "%=", "&=", "^=", "|=",
+    "+", "-", "*", "/", "%", "<", ">", "&", "^", "|", "!", "~", "=", "?", ":", "(", ")", ","
+];
+
+const ASSIGNMENTS: [&str; 11] = ["=", "+=", "-=", "*=", "/=", "%=", "<<=", ">>=", "&=", "^=", "|="];
+
+// The binary operators from the loosest to the tightest binding; `**` is
+// tighter still and groups from the right.
+const LEVELS: [&[&str]; 10] = [
+    &["||"],
+    &["&&"],
+    &["|"],
+    &["^"],
+    &["&"],
+    &["==", "!="],
+    &["<", ">", "<=", ">="],
+    &["<<", ">>"],
+    &["+", "-"],
+    &["*", "/", "%"]
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Number(i64),
+    Name(String),
+    Operator(&'static str)
+}
+
+impl Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Number(value) => write!(f, "{}", value),
+            Token::Name(name) => write!(f, "{}", name),
+            Token::Operator(operator) => write!(f, "{}", operator)
+        }
+    }
+}
+
+/// Evaluates an integer expression as `$((...))` and `let` do, with the
+/// operators, precedence and assignments of C. Unset and empty variables
+/// count as zero.
+pub fn evaluate(input: &mut Input, expression: &str) -> Result<i64, String> {
+    evaluate_at(input, expression, 0).map_err(|err| format!("{}: {}", expression.trim(), err))
+}
+
+fn evaluate_at(input: &mut Input, expression: &str, depth: usize) -> Result<i64, String> {
+    if depth > MAX_DEPTH {
+        return Err("expression recursion level exceeded".into());
+    }
+
+    let tokens = tokenize(expression)?;
+    if tokens.is_empty() {
+        return Ok(0);
+    }
+
+    let mut evaluator = Evaluator {
+        input,
+        tokens,
+        position: 0,
+        skip: 0,
+        depth
+    };
+
+    let value = evaluator.comma()?;
+    match evaluator.tokens.get(evaluator.position) {
+        Some(token) => Err(format!("unexpected `{}`", token)),
+        None => Ok(value)
+    }
+}
+
+fn tokenize(expression: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = vec![];
+    let mut rest = expression.trim_start();
+    while let Some(c) = rest.chars().next() {
+        let length = if c.is_ascii_digit() {
+            let length = rest
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '#' && c != '_')
+                .unwrap_or(rest.len());
+
+            tokens.push(Token::Number(number(&rest[..length])?));
+            length
+        } else if c == '_' || c.is_ascii_alphabetic() {
+            let length = rest
+                .find(|c: char| c != '_' && !c.is_ascii_alphanumeric())
+                .unwrap_or(rest.len());
+
+            tokens.push(Token::Name(rest[..length].into()));
+            length
+        } else {
+            match OPERATORS.iter().find(|operator| rest.starts_with(*operator)) {
+                Some(operator) => {
+                    tokens.push(Token::Operator(operator));
+                    operator.len()
+                },
+                None => return Err(format!("unexpected `{}`", c))
+            }
+        };
+
+        rest = rest[length..].trim_start();
+    }
+
+    Ok(tokens)
+}
+
+// Numbers are decimal, octal with a leading `0`, hexadecimal with `0x`, or
+// in any base from 2 to 64 as `base#digits`.
+fn number(text: &str) -> Result<i64, String> {
+    let (base, digits) = match text.split_once('#') {
+        Some((base, digits)) => match base.parse::<u32>() {
+            Ok(base) if (2..=64).contains(&base) => (base, digits),
+            _ => return Err("invalid arithmetic base".into())
+        },
+        None => match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+            Some(digits) => (16, digits),
+            None if text.len() > 1 && text.starts_with('0') => (8, &text[1..]),
+            None => (10, text)
+        }
+    };
+
+    if digits.is_empty() {
+        return Err("invalid number".into());
+    }
+
+    let mut value: i64 = 0;
+    for c in digits.chars() {
+        // Up to base 36 letters count the same in either case, above that
+        // lower case comes first, then upper case, `@` and `_`.
+        let digit = match c {
+            '0'..='9' => c as u32 - '0' as u32,
+            'a'..='z' => c as u32 - 'a' as u32 + 10,
+            'A'..='Z' if base <= 36 => c as u32 - 'A' as u32 + 10,
+            'A'..='Z' => c as u32 - 'A' as u32 + 36,
+            '@' => 62,
+            '_' => 63,
+            _ => base
+        };
+
+        if digit >= base {
+            return Err("value too great for base".into());
+        }
+
+        value = value
+            .checked_mul(base.into())
+            .and_then(|value| value.checked_add(digit.into()))
+            .ok_or("integer overflow")?;
+    }
+
+    Ok(value)
+}
+
+struct Evaluator<'a, 'b> {
+    input: &'a mut Input<'b>,
+    tokens: Vec<Token>,
+    position: usize,
+    // Above zero in the part `&&`, `||` or `?:` leaves out, which is parsed
+    // but neither assigns nor fails.
+    skip: usize,
+    depth: usize
+}
+
+impl Evaluator<'_, '_> {
+    fn peek(&self, offset: usize) -> Option<&Token> {
+        self.tokens.get(self.position + offset)
+    }
+
+    fn eat(&mut self, operators: &[&str]) -> Option<&'static str> {
+        match self.peek(0) {
+            Some(&Token::Operator(operator)) if operators.contains(&operator) => {
+                self.position += 1;
+                Some(operator)
+            },
+            _ => None
+        }
+    }
+
+    fn expect(&mut self, operator: &str) -> Result<(), String> {
+        match self.eat(&[operator]) {
+            Some(_) => Ok(()),
+            None => Err(match self.peek(0) {
+                Some(token) => format!("expected `{}` instead of `{}`", operator, token),
+                None => format!("expected `{}`", operator)
+            })
+        }
+    }
+
+    fn comma(&mut self) -> Result<i64, String> {
+        let mut value = self.assignment()?;
+        while self.eat(&[","]).is_some() {
+            value = self.assignment()?;
+        }
+
+        Ok(value)
+    }
+
+    fn assignment(&mut self) -> Result<i64, String> {
+        let target = match (self.peek(0), self.peek(1)) {
+            (Some(Token::Name(name)), Some(Token::Operator(operator))) if ASSIGNMENTS.contains(operator) => {
+                Some((name.clone(), *operator))
+            },
+            _ => None
+        };
+
+        let (name, operator) = match target {
+            Some(target) => target,
+            None => return self.conditional()
+        };
+
+        self.position += 2;
+        let value = self.assignment()?;
+        let value = match operator.strip_suffix('=') {
+            Some("") => value,
+            Some(operator) => {
+                let current = self.variable(&name)?;
+                self.apply(operator, current, value)?
+            },
+            None => unreachable!("not an assignment: {}", operator)
+        };
+
+        self.assign(&name, value);
+        Ok(value)
+    }
+
+    fn conditional(&mut self) -> Result<i64, String> {
+        let condition = self.binary(0)?;
+        if self.eat(&["?"]).is_none() {
+            return Ok(condition);
+        }
+
+        let then = self.skipping(condition == 0, Self::comma)?;
+        self.expect(":")?;
+        let otherwise = self.skipping(condition != 0, Self::assignment)?;
+
+        Ok(if condition != 0 { then } else { otherwise })
+    }
+
+    fn binary(&mut self, level: usize) -> Result<i64, String> {
+        let operators = match LEVELS.get(level) {
+            Some(operators) => operators,
+            None => return self.power()
+        };
+
+        let mut left = self.binary(level + 1)?;
+        while let Some(operator) = self.eat(operators) {
+            // Once the left side decides `&&` or `||`, the right one is not
+            // evaluated.
+            let skip = (operator == "&&" && left == 0) || (operator == "||" && left != 0);
+            let right = self.skipping(skip, |evaluator| evaluator.binary(level + 1))?;
+            left = self.apply(operator, left, right)?;
+        }
+
+        Ok(left)
+    }
+
+    fn power(&mut self) -> Result<i64, String> {
+        let base = self.unary()?;
+        match self.eat(&["**"]) {
+            Some(operator) => {
+                let exponent = self.power()?;
+                self.apply(operator, base, exponent)
+            },
+            None => Ok(base)
+        }
+    }
+
+    fn unary(&mut self) -> Result<i64, String> {
+        if let (Some(&Token::Operator(operator @ ("++" | "--"))), Some(Token::Name(name))) = (self.peek(0), self.peek(1)) {
+            let (operator, name) = (&operator[..1], name.clone());
+            self.position += 2;
+
+            let value = self.variable(&name)?;
+            let value = self.apply(operator, value, 1)?;
+            self.assign(&name, value);
+
+            return Ok(value);
+        }
+
+        // Anywhere else `--` and `++` are two signs in a row.
+        match self.eat(&["-", "+", "!", "~", "--", "++"]) {
+            Some("-") => {
+                let value = self.unary()?;
+                self.apply("-", 0, value)
+            },
+            Some("+") => self.unary(),
+            Some("!") => Ok((self.unary()? == 0).into()),
+            Some("~") => Ok(!self.unary()?),
+            Some("--") => {
+                let value = self.unary()?;
+                let value = self.apply("-", 0, value)?;
+                self.apply("-", 0, value)
+            },
+            Some(_) => self.unary(),
+            None => self.postfix()
+        }
+    }
+
+    fn postfix(&mut self) -> Result<i64, String> {
+        if let (Some(Token::Name(name)), Some(&Token::Operator(operator @ ("++" | "--")))) = (self.peek(0), self.peek(1)) {
+            let (operator, name) = (&operator[..1], name.clone());
+            self.position += 2;
+
+            let value = self.variable(&name)?;
+            let next = self.apply(operator, value, 1)?;
+            self.assign(&name, next);
+
+            return Ok(value);
+        }
+
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<i64, String> {
+        let token = match self.peek(0) {
+            Some(token) => token.clone(),
+            None => return Err("expected an operand".into())
+        };
+
+        self.position += 1;
+        match token {
+            Token::Number(value) => Ok(value),
+            Token::Name(name) => self.variable(&name),
+            Token::Operator("(") => {
+                let value = self.comma()?;
+                self.expect(")")?;
+
+                Ok(value)
+            },
+            Token::Operator(operator) => Err(format!("unexpected `{}`", operator))
+        }
+    }
+
+    fn skipping(&mut self, skip: bool, parse: impl FnOnce(&mut Self) -> Result<i64, String>) -> Result<i64, String> {
+        if skip {
+            self.skip += 1;
+        }
+
+        let value = parse(self);
+        if skip {
+            self.skip -= 1;
+        }
+
+        value
+    }
+
+    // The value of a variable is an expression itself, usually a number.
+    fn variable(&mut self, name: &str) -> Result<i64, String> {
+        let value = self.input.var(name).unwrap_or_default();
+        match value.trim().parse() {
+            Ok(value) => Ok(value),
+            Err(_) if self.skip > 0 => Ok(0),
+            Err(_) => evaluate_at(self.input, &value, self.depth + 1)
+        }
+    }
+
+    fn assign(&mut self, name: &str, value: i64) {
+        if self.skip == 0 {
+            self.input.set_var(name, value.to_string());
+        }
+    }
+
+    fn apply(&self, operator: &str, left: i64, right: i64) -> Result<i64, String> {
+        if self.skip > 0 {
+            return Ok(0);
+        }
+
+        let value = match operator {
+            "+" => left.checked_add(right),
+            "-" => left.checked_sub(right),
+            "*" => left.checked_mul(right),
+            "/" | "%" if right == 0 => return Err("division by zero".into()),
+            "/" => left.checked_div(right),
+            "%" => left.checked_rem(right),
+            "**" if right < 0 => return Err("exponent less than zero".into()),
+            "**" => u32::try_from(right).ok().and_then(|right| left.checked_pow(right)),
+            "<<" | ">>" if !(0..64).contains(&right) => return Err("shift count out of range".into()),
+            "<<" => left.checked_shl(right as u32),
+            ">>" => left.checked_shr(right as u32),
+            "&" => Some(left & right),
+            "^" => Some(left ^ right),
+            "|" => Some(left | right),
+            "&&" => Some((left != 0 && right != 0).into()),
+            "||" => Some((left != 0 || right != 0).into()),
+            "==" => Some((left == right).into()),
+            "!=" => Some((left != right).into()),
+            "<" => Some((left < right).into()),
+            ">" => Some((left > right).into()),
+            "<=" => Some((left <= right).into()),
+            ">=" => Some((left >= right).into()),
+            _ => unreachable!("not a binary operator: {}", operator)
+        };
+
+        value.ok_or_else(|| "integer overflow".into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(input: &mut Input, expression: &str) -> i64 {
+        evaluate(input, expression).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    fn error(expression: &str) -> String {
+        let mut input = Input::script(vec!["sks".into()]);
+        match evaluate(&mut input, expression) {
+            Ok(value) => panic!("{} evaluated to {}", expression, value),
+            Err(err) => err
+        }
+    }
+
+    #[test]
+    fn tokens() {
+        assert_eq!(tokenize("a<<=2**b").unwrap(), vec![
+            Token::Name("a".into()),
+            Token::Operator("<<="),
+            Token::Number(2),
+            Token::Operator("**"),
+            Token::Name("b".into())
+        ]);
+        assert_eq!(tokenize("  ").unwrap(), vec![]);
+        assert_eq!(tokenize("1 $ 2").unwrap_err(), "unexpected `$`");
+    }
+
+    #[test]
+    fn bases() {
+        assert_eq!(number("42"), Ok(42));
+        assert_eq!(number("017"), Ok(15));
+        assert_eq!(number("0x1f"), Ok(31));
+        assert_eq!(number("0XFF"), Ok(255));
+        assert_eq!(number("2#101"), Ok(5));
+        assert_eq!(number("36#zZ"), Ok(35 * 36 + 35));
+        assert_eq!(number("64#_@"), Ok(63 * 64 + 62));
+        assert_eq!(number("64#aA"), Ok(10 * 64 + 36));
+        assert_eq!(number("08"), Err("value too great for base".into()));
+        assert_eq!(number("1#0"), Err("invalid arithmetic base".into()));
+        assert_eq!(number("0x"), Err("invalid number".into()));
+        assert_eq!(number("9223372036854775808"), Err("integer overflow".into()));
+    }
+
+    #[test]
+    fn precedence() {
+        let mut input = Input::script(vec!["sks".into()]);
+        assert_eq!(eval(&mut input, "1 + 2 * 3"), 7);
+        assert_eq!(eval(&mut input, "(1 + 2) * 3"), 9);
+        assert_eq!(eval(&mut input, "2 ** 3 ** 2"), 512);
+        assert_eq!(eval(&mut input, "-2 ** 2"), 4);
+        assert_eq!(eval(&mut input, "10 - 4 - 3"), 3);
+        assert_eq!(eval(&mut input, "1 << 2 + 1"), 8);
+        assert_eq!(eval(&mut input, "1 | 2 ^ 3 & 6"), 1);
+        assert_eq!(eval(&mut input, "1 < 2 == 1"), 1);
+        assert_eq!(eval(&mut input, "0 || 2 && 3"), 1);
+        assert_eq!(eval(&mut input, "1 ? 2 : 0 ? 3 : 4"), 2);
+        assert_eq!(eval(&mut input, "!0 + ~0"), 0);
+        assert_eq!(eval(&mut input, "-7 / 2, -7 % 2"), -1);
+        assert_eq!(eval(&mut input, ""), 0);
+    }
+
+    #[test]
+    fn variables() {
+        let mut input = Input::script(vec!["sks".into()]);
+        assert_eq!(eval(&mut input, "unset + 1"), 1);
+        assert_eq!(eval(&mut input, "a = b = 3"), 3);
+        assert_eq!(eval(&mut input, "a += 2, a *= b"), 15);
+        assert_eq!(eval(&mut input, "a++ + ++b"), 19);
+        assert_eq!(input.var("a").as_deref(), Some("16"));
+        assert_eq!(input.var("b").as_deref(), Some("4"));
+        assert_eq!(eval(&mut input, "--a - b--"), 11);
+        assert_eq!(eval(&mut input, "c <<= 1"), 0);
+
+        input.set_var("e", "b * 2");
+        assert_eq!(eval(&mut input, "e + 1"), 7);
+
+        input.set_var("r", "r");
+        assert!(evaluate(&mut input, "r").unwrap_err().ends_with("expression recursion level exceeded"));
+    }
+
+    #[test]
+    fn short_circuit() {
+        let mut input = Input::script(vec!["sks".into()]);
+        assert_eq!(eval(&mut input, "0 && (x = 1)"), 0);
+        assert_eq!(eval(&mut input, "1 || (x = 1)"), 1);
+        assert_eq!(eval(&mut input, "1 ? 2 : (x = 3)"), 2);
+        assert_eq!(eval(&mut input, "0 ? x++ : 4"), 4);
+        assert_eq!(input.var("x"), None);
+
+        assert_eq!(eval(&mut input, "0 && 1 / 0"), 0);
+        assert_eq!(eval(&mut input, "1 || 2 ** -1"), 1);
+
+        input.set_var("bad", "1 +");
+        assert_eq!(eval(&mut input, "0 && bad"), 0);
+        assert_eq!(eval(&mut input, "1 && (y = 5)"), 1);
+        assert_eq!(input.var("y").as_deref(), Some("5"));
+    }
+
+    #[test]
+    fn failures() {
+        assert_eq!(error("1 / 0"), "1 / 0: division by zero");
+        assert_eq!(error("5 % (2 - 2)"), "5 % (2 - 2): division by zero");
+        assert_eq!(error("9223372036854775807 + 1"), "9223372036854775807 + 1: integer overflow");
+        assert_eq!(error("-9223372036854775807 - 2"), "-9223372036854775807 - 2: integer overflow");
+        assert_eq!(error("2 ** 64"), "2 ** 64: integer overflow");
+        assert_eq!(error("2 ** -1"), "2 ** -1: exponent less than zero");
+        assert_eq!(error("1 << 64"), "1 << 64: shift count out of range");
+        assert_eq!(error("1 +"), "1 +: expected an operand");
+        assert_eq!(error("(1"), "(1: expected `)`");
+        assert_eq!(error("1 ? 2"), "1 ? 2: expected `:`");
+        assert_eq!(error("1 2"), "1 2: unexpected `2`");
+        assert_eq!(error("3 = 4"), "3 = 4: unexpected `=`");
+    }
+}
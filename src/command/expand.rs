@@ -0,0 +1,329 @@
+use std::borrow::Cow;
+
+use crate::{data::Input, format::{Format, ast::{Command, SimpleCommand, Word, WordPart, Parameter, Modifier, is_name}}};
+
+use super::{Flow, glob, arithmetic::evaluate, interpreter::capture};
+
+/// Expands a word into the fields the command receives. Results of
+/// unquoted expansions are split on whitespace, quoted ones never are, and
+/// a field with an unquoted pattern in it becomes the files it matches.
+pub fn expand(input: &mut Input, word: &Word) -> Result<Vec<String>, String> {
+    let mut fields = Fields::new(true, true);
+    expand_parts(input, &word.parts, false, &mut fields)?;
+
+    let mut words = vec![];
+    for field in fields.finish() {
+        if !glob::is_pattern(&field) {
+            words.push(glob::unescape(&field));
+            continue;
+        }
+
+        let paths = glob::expand(input.path(), &field);
+        match (paths.is_empty(), input.failglob()) {
+            (false, _) => words.extend(paths),
+            (true, true) => return Err(format!("no match: {}", glob::unescape(&field))),
+            (true, false) => words.push(glob::unescape(&field))
+        }
+    }
+
+    Ok(words)
+}
+
+/// Expands a word into a pattern for `glob::matches`, in which everything
+/// that was quoted only matches itself.
+pub fn expand_pattern(input: &mut Input, word: &Word) -> Result<String, String> {
+    let mut fields = Fields::new(false, true);
+    expand_parts(input, &word.parts, false, &mut fields)?;
+
+    Ok(fields.finish().pop().unwrap_or_default())
+}
+
+/// Expands a word into exactly one string, as needed for assignments and
+/// redirection targets.
+pub fn expand_string(input: &mut Input, word: &Word) -> Result<String, String> {
+    expand_parts_string(input, &word.parts)
+}
+
+fn expand_parts_string(input: &mut Input, parts: &[WordPart]) -> Result<String, String> {
+    let mut fields = Fields::new(false, false);
+    expand_parts(input, parts, false, &mut fields)?;
+
+    Ok(fields.finish().pop().unwrap_or_default())
+}
+
+/// Replaces an unquoted command name that is an alias by what it stands
+/// for, again and again while that starts with another alias. An alias is
+/// not expanded inside its own expansion, so `alias ls='ls -F'` works. When
+/// a value ends in a blank, the word after it is checked for an alias too.
+pub fn expand_aliases(input: &Input, command: &SimpleCommand) -> Result<SimpleCommand, String> {
+    let mut command = command.clone();
+    let mut seen: Vec<String> = vec![];
+    // How many words follow the one to check once the current chain of
+    // aliases is done, if one of them ended in a blank.
+    let mut next = None;
+    let mut idx = 0;
+    loop {
+        let found = command.words
+            .get(idx)
+            .and_then(Word::literal)
+            .map(str::to_string)
+            .filter(|name| !seen.contains(name))
+            .and_then(|name| input.alias(&name).map(|value| (name, value.to_string())));
+
+        let (name, value) = match found {
+            Some(found) => found,
+            None => match next.take() {
+                Some(rest) => {
+                    idx = command.words.len() - rest;
+                    seen.clear();
+                    continue;
+                },
+                None => break
+            }
+        };
+
+        let alias = parse_alias(&name, &value)?;
+        let rest = command.words.split_off(idx + 1);
+        command.words.pop();
+        if value.ends_with([' ', '\t']) {
+            next = Some(rest.len());
+        }
+
+        command.assignments.extend(alias.assignments);
+        command.words.extend(alias.words);
+        command.redirects.extend(alias.redirects);
+        command.words.extend(rest);
+        seen.push(name);
+    }
+
+    Ok(command)
+}
+
+/// Parses the value of an alias, which has to be a simple command.
+pub fn parse_alias(name: &str, value: &str) -> Result<SimpleCommand, String> {
+    let program = Format::from(value)
+        .into_program()
+        .map_err(|err| format!("{}: {}", name, err))?;
+
+    let error = || format!("{}: an alias can only stand for a simple command", name);
+    let mut items = program.list.items.into_iter();
+    let and_or = match (items.next(), items.next()) {
+        (None, _) => return Ok(SimpleCommand {
+            assignments: vec![],
+            words: vec![],
+            redirects: vec![]
+        }),
+        (Some(and_or), None) if and_or.rest.is_empty() && !and_or.background => and_or,
+        _ => return Err(error())
+    };
+
+    let mut commands = and_or.first.commands.into_iter();
+    match (commands.next(), commands.next()) {
+        (Some(Command::Simple(command)), None) => Ok(command),
+        _ => Err(error())
+    }
+}
+
+struct Fields {
+    fields: Vec<String>,
+    current: String,
+    started: bool,
+    split: bool,
+    pattern: bool
+}
+
+impl Fields {
+    fn new(split: bool, pattern: bool) -> Self {
+        Self {
+            fields: vec![],
+            current: String::new(),
+            started: false,
+            split,
+            pattern
+        }
+    }
+
+    fn push(&mut self, text: &str) {
+        self.current.push_str(text);
+        self.started = true;
+    }
+
+    // Quoted text is never a pattern, so in one its special characters
+    // are escaped.
+    fn push_quoted(&mut self, text: &str) {
+        if !self.pattern {
+            return self.push(text);
+        }
+
+        for c in text.chars() {
+            if matches!(c, '*' | '?' | '[' | ']' | '\\') {
+                self.current.push('\\');
+            }
+
+            self.current.push(c);
+        }
+
+        self.started = true;
+    }
+
+    // Unquoted results of expansions are still patterns, but a backslash in
+    // them is an ordinary character.
+    fn push_split(&mut self, text: &str) {
+        let text = match self.pattern {
+            true => Cow::Owned(text.replace('\\', "\\\\")),
+            false => Cow::Borrowed(text)
+        };
+
+        if !self.split {
+            return self.push(&text);
+        }
+
+        if text.starts_with(char::is_whitespace) {
+            self.end();
+        }
+
+        let mut pieces = text.split_whitespace().peekable();
+        while let Some(piece) = pieces.next() {
+            self.push(piece);
+            if pieces.peek().is_some() {
+                self.end();
+            }
+        }
+
+        if text.ends_with(char::is_whitespace) {
+            self.end();
+        }
+    }
+
+    fn end(&mut self) {
+        if self.started {
+            self.fields.push(std::mem::take(&mut self.current));
+            self.started = false;
+        }
+    }
+
+    fn finish(mut self) -> Vec<String> {
+        self.end();
+        self.fields
+    }
+}
+
+fn expand_parts(input: &mut Input, parts: &[WordPart], quoted: bool, fields: &mut Fields) -> Result<(), String> {
+    for part in parts {
+        match part {
+            WordPart::Literal(s) if !quoted => fields.push(s),
+            WordPart::Literal(s) | WordPart::SingleQuoted(s) => fields.push_quoted(s),
+            WordPart::Escaped(c) => fields.push_quoted(c.encode_utf8(&mut [0; 4])),
+            WordPart::DoubleQuoted(parts) => {
+                // `""` is still an (empty) argument, but `"$@"` is none at
+                // all when there are no positional parameters.
+                let arguments = matches!(parts.as_slice(), [WordPart::Parameter(parameter)] if is_arguments(parameter));
+                if !arguments || input.arguments().len() > 1 {
+                    fields.push("");
+                }
+
+                expand_parts(input, parts, true, fields)?;
+            },
+            // Quoted, every positional parameter is a field of its own.
+            WordPart::Parameter(parameter) if quoted && fields.split && is_arguments(parameter) => {
+                for (idx, argument) in input.arguments()[1..].iter().enumerate() {
+                    if idx > 0 {
+                        fields.end();
+                    }
+
+                    fields.push_quoted(argument);
+                }
+            },
+            WordPart::Parameter(parameter) => {
+                let value = expand_parameter(input, parameter)?;
+                if quoted {
+                    fields.push_quoted(&value);
+                } else {
+                    fields.push_split(&value);
+                }
+            },
+            WordPart::Command { program, .. } => {
+                let output = capture(input, program)?;
+                if quoted {
+                    fields.push_quoted(&output);
+                } else {
+                    fields.push_split(&output);
+                }
+            },
+            WordPart::Arithmetic(parts) => {
+                let expression = expand_parts_string(input, parts)?;
+                fields.push(&evaluate(input, &expression)?.to_string());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn is_arguments(parameter: &Parameter) -> bool {
+    parameter.name == "@" && parameter.modifier.is_none()
+}
+
+fn expand_parameter(input: &mut Input, parameter: &Parameter) -> Result<String, String> {
+    let name = parameter.name.as_str();
+    let value = lookup(input, name);
+    let is_set = |colon: &bool| match &value {
+        Some(value) => !*colon || !value.is_empty(),
+        None => false
+    };
+
+    match &parameter.modifier {
+        None => Ok(value.unwrap_or_default()),
+        Some(Modifier::Length) => Ok(value.map_or(0, |value| value.chars().count()).to_string()),
+        Some(Modifier::Default { colon, word }) => if is_set(colon) {
+            Ok(value.unwrap_or_default())
+        } else {
+            expand_parts_string(input, word)
+        },
+        Some(Modifier::Assign { colon, word }) => if is_set(colon) {
+            Ok(value.unwrap_or_default())
+        } else if !is_name(name) {
+            Err(format!("${{{}}}: cannot assign in this way", name))
+        } else {
+            let value = expand_parts_string(input, word)?;
+            input.set_var(name, value.clone());
+
+            Ok(value)
+        },
+        Some(Modifier::Alternative { colon, word }) => if is_set(colon) {
+            expand_parts_string(input, word)
+        } else {
+            Ok(String::new())
+        },
+        Some(Modifier::Error { colon, word }) => if is_set(colon) {
+            Ok(value.unwrap_or_default())
+        } else {
+            // Only the user can still do something about the parameter, a
+            // script stops here as if it ran `exit`.
+            let message = expand_parts_string(input, word)?;
+            if !input.interactive() {
+                input.set_flow(Some(Flow::Exit));
+            }
+
+            Err(match message.is_empty() {
+                true => format!("{}: parameter null or not set", name),
+                false => format!("{}: {}", name, message)
+            })
+        }
+    }
+}
+
+fn lookup(input: &Input, name: &str) -> Option<String> {
+    match name {
+        "?" => Some(input.status().to_string()),
+        "$" => Some(std::process::id().to_string()),
+        "!" => input.last_background().map(|pid| pid.to_string()),
+        "#" => Some((input.arguments().len() - 1).to_string()),
+        "@" | "*" => Some(input.arguments()[1..].join(" ")),
+        _ if name.chars().all(|c| c.is_ascii_digit()) => name
+            .parse::<usize>()
+            .ok()
+            .and_then(|idx| input.arguments().get(idx).cloned()),
+        _ => input.var(name)
+    }
+}
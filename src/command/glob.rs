@@ -0,0 +1,356 @@
+use std::path::Path;
+
+/// Whether `name` matches the shell pattern: `*` matches any run of
+/// characters, `?` any single one and `[...]` one out of a set, which may
+/// hold `a-z` ranges and is negated by a leading `!` or `^`. A backslash
+/// takes the next character literally.
+pub fn matches(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let name = name.chars().collect::<Vec<_>>();
+
+    let (mut p, mut n) = (0, 0);
+    // Where the last `*` was seen and how much of the name it took, so a
+    // failed match can let it take one character more and retry.
+    let mut star = None;
+    while n < name.len() {
+        let step = match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+                continue;
+            },
+            Some('?') => Some(1),
+            Some('[') => match class(&pattern, p, name[n]) {
+                Some((true, length)) => Some(length),
+                Some((false, _)) => None,
+                None => (name[n] == '[').then_some(1)
+            },
+            Some('\\') if p + 1 < pattern.len() => (pattern[p + 1] == name[n]).then_some(2),
+            Some(&c) => (c == name[n]).then_some(1),
+            None => None
+        };
+
+        match (step, star) {
+            (Some(step), _) => {
+                p += step;
+                n += 1;
+            },
+            (None, Some((star_p, star_n))) => {
+                p = star_p + 1;
+                n = star_n + 1;
+                star = Some((star_p, star_n + 1));
+            },
+            (None, None) => return false
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+// Matches `c` against the set starting at `pattern[start] == '['` and
+// returns whether it matched and how long the set is, or `None` when the
+// set is never closed and the `[` is an ordinary character.
+fn class(pattern: &[char], start: usize, c: char) -> Option<(bool, usize)> {
+    let mut idx = start + 1;
+    let negated = matches!(pattern.get(idx), Some('!' | '^'));
+    if negated {
+        idx += 1;
+    }
+
+    let mut matched = false;
+    let mut first = true;
+    loop {
+        let current = *pattern.get(idx)?;
+        if current == ']' && !first {
+            return Some((matched != negated, idx + 1 - start));
+        }
+
+        first = false;
+        match (pattern.get(idx + 1), pattern.get(idx + 2)) {
+            (Some('-'), Some(&end)) if end != ']' => {
+                matched |= current <= c && c <= end;
+                idx += 3;
+            },
+            _ => {
+                matched |= current == c;
+                idx += 1;
+            }
+        }
+    }
+}
+
+/// Whether `pattern` has an unescaped `*`, `?` or closed `[...]`, which
+/// makes it something to match file names against.
+pub fn is_pattern(pattern: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let mut idx = 0;
+    while idx < pattern.len() {
+        match pattern[idx] {
+            '\\' => idx += 1,
+            '*' | '?' => return true,
+            '[' if class(&pattern, idx, '\0').is_some() => return true,
+            _ => ()
+        }
+
+        idx += 1;
+    }
+
+    false
+}
+
+/// Drops the backslashes that make the characters after them literal.
+pub fn unescape(pattern: &str) -> String {
+    let mut result = String::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => result.extend(chars.next()),
+            c => result.push(c)
+        }
+    }
+
+    result
+}
+
+/// The paths that match `pattern`, relative to `dir` unless the pattern is
+/// absolute, sorted. Every part between slashes is matched on its own, a
+/// `**` part stands for any number of directories and names that start
+/// with a dot are only matched by a part that starts with one too.
+pub fn expand(dir: &Path, pattern: &str) -> Vec<String> {
+    let (mut found, rest) = match pattern.strip_prefix('/') {
+        Some(rest) => (vec!["/".to_string()], rest),
+        None => (vec![String::new()], pattern)
+    };
+
+    let parts = rest.split('/').collect::<Vec<_>>();
+    for (idx, part) in parts.iter().enumerate() {
+        let last = idx + 1 == parts.len();
+        let mut next = vec![];
+        for prefix in &found {
+            match *part {
+                // A trailing slash only keeps the directories.
+                "" if last => if dir.join(prefix).is_dir() && !prefix.is_empty() {
+                    next.push(format!("{}/", prefix.trim_end_matches('/')));
+                },
+                "" => next.push(prefix.clone()),
+                "**" => {
+                    if !last {
+                        next.push(prefix.clone());
+                    }
+
+                    walk(dir, prefix, last, &mut next);
+                },
+                part if is_pattern(part) => if let Ok(entries) = dir.join(prefix).read_dir() {
+                    next.extend(entries
+                        .filter_map(Result::ok)
+                        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+                        .filter(|name| !name.starts_with('.') || part.starts_with('.'))
+                        .filter(|name| matches(part, name))
+                        .map(|name| join(prefix, &name))
+                        .filter(|path| last || dir.join(path).is_dir()));
+                },
+                part => {
+                    let path = join(prefix, &unescape(part));
+                    if dir.join(&path).symlink_metadata().is_ok() {
+                        next.push(path);
+                    }
+                }
+            }
+        }
+
+        found = next;
+    }
+
+    found.sort();
+    found.dedup();
+    found
+}
+
+// Collects what is below `prefix`: the directories, and the files as well
+// when `files` is set. Links are not followed, so a loop ends.
+fn walk(dir: &Path, prefix: &str, files: bool, found: &mut Vec<String>) {
+    let entries = match dir.join(prefix).read_dir() {
+        Ok(entries) => entries,
+        Err(_) => return
+    };
+
+    for entry in entries.filter_map(Result::ok) {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.starts_with('.') {
+            continue;
+        }
+
+        let path = join(prefix, &name);
+        let is_dir = entry.file_type().is_ok_and(|kind| kind.is_dir());
+        if is_dir || files {
+            found.push(path.clone());
+        }
+
+        if is_dir {
+            walk(dir, &path, files, found);
+        }
+    }
+}
+
+fn join(prefix: &str, name: &str) -> String {
+    match prefix.is_empty() || prefix.ends_with('/') {
+        true => format!("{}{}", prefix, name),
+        false => format!("{}/{}", prefix, name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use super::*;
+
+    #[test]
+    fn stars_take_any_run_of_characters() {
+        assert!(matches("*.rs", "main.rs"));
+        assert!(!matches("*.rs", "main.rsx"));
+        assert!(matches("*", ""));
+        assert!(matches("a*", "a"));
+        assert!(matches("**", "anything"));
+        assert!(!matches("a*", "ba"));
+    }
+
+    #[test]
+    fn a_failed_match_lets_the_star_take_more() {
+        assert!(matches("*ab", "aab"));
+        assert!(matches("a*b*c", "aXbYbZc"));
+        assert!(matches("*a*a", "banana"));
+        assert!(!matches("*a*b", "banana"));
+        assert!(matches("*?x", "yyx"));
+        assert!(!matches("*?x", "x"));
+    }
+
+    #[test]
+    fn question_marks_take_one_character() {
+        assert!(matches("?.?", "a.b"));
+        assert!(!matches("?", ""));
+        assert!(!matches("?", "ab"));
+        assert!(matches("?", "é"));
+        assert!(matches("?ß?", "äßö"));
+    }
+
+    #[test]
+    fn sets_and_ranges() {
+        assert!(matches("[abc]", "b"));
+        assert!(!matches("[abc]", "d"));
+        assert!(matches("[a-c]x", "bx"));
+        assert!(!matches("[a-c]x", "dx"));
+        assert!(matches("[α-ω]", "β"));
+        assert!(matches("[!a-c]", "d"));
+        assert!(!matches("[!a-c]", "b"));
+        assert!(matches("[^a]", "b"));
+        assert!(!matches("[^a]", "a"));
+    }
+
+    #[test]
+    fn brackets_and_dashes_in_sets() {
+        // A `]` right after the opening `[` (or `[!`) belongs to the set.
+        assert!(matches("[]a]", "]"));
+        assert!(matches("[]a]", "a"));
+        assert!(!matches("[]a]", "b"));
+        assert!(matches("[!]]", "x"));
+        assert!(!matches("[!]]", "]"));
+
+        // A `-` at the end is just a dash.
+        assert!(matches("[a-]", "-"));
+        assert!(matches("[a-]", "a"));
+        assert!(!matches("[a-]", "b"));
+    }
+
+    #[test]
+    fn an_unclosed_set_is_literal() {
+        assert!(matches("[ab", "[ab"));
+        assert!(!matches("[ab", "a"));
+        assert!(matches("[", "["));
+        assert!(!is_pattern("[ab"));
+        assert!(is_pattern("[ab]"));
+    }
+
+    #[test]
+    fn backslashes_make_characters_literal() {
+        assert!(matches("\\*", "*"));
+        assert!(!matches("\\*", "x"));
+        assert!(matches("a\\?", "a?"));
+        assert!(!matches("a\\?", "ab"));
+        assert!(matches("\\[a]", "[a]"));
+        assert!(!is_pattern("\\*\\?\\[a]"));
+        assert!(is_pattern("a\\*?"));
+        assert_eq!(unescape("a\\*b\\\\c\\"), "a*b\\c");
+    }
+
+    // A directory of its own for every test that lists files.
+    struct Tree(PathBuf);
+
+    impl Tree {
+        fn new(name: &str, files: &[&str]) -> Self {
+            let root = std::env::temp_dir().join(format!("sks-glob-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&root);
+            for file in files {
+                let path = root.join(file);
+                match file.strip_suffix('/') {
+                    Some(_) => fs::create_dir_all(&path).unwrap(),
+                    None => {
+                        fs::create_dir_all(path.parent().unwrap()).unwrap();
+                        fs::write(&path, "").unwrap();
+                    }
+                }
+            }
+
+            Tree(root)
+        }
+    }
+
+    impl Drop for Tree {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    const FILES: [&str; 7] = ["a.txt", "b.txt", ".hidden", "sub/c.txt", "sub/.d.txt", "sub/deep/e.txt", "empty/"];
+
+    #[test]
+    fn expands_each_part_of_a_path() {
+        let tree = Tree::new("parts", &FILES);
+        assert_eq!(expand(&tree.0, "*.txt"), ["a.txt", "b.txt"]);
+        assert_eq!(expand(&tree.0, "*"), ["a.txt", "b.txt", "empty", "sub"]);
+        assert_eq!(expand(&tree.0, "s*/*.txt"), ["sub/c.txt"]);
+        assert_eq!(expand(&tree.0, "*/deep"), ["sub/deep"]);
+        assert_eq!(expand(&tree.0, "sub/[cd]*"), ["sub/c.txt", "sub/deep"]);
+        assert!(expand(&tree.0, "*.rs").is_empty());
+        assert!(expand(&tree.0, "missing/*").is_empty());
+
+        let pattern = format!("{}/?.txt", tree.0.display());
+        let found = ["a.txt", "b.txt"].map(|name| format!("{}/{}", tree.0.display(), name));
+        assert_eq!(expand(Path::new("/"), &pattern), found);
+    }
+
+    #[test]
+    fn dotfiles_need_a_leading_dot() {
+        let tree = Tree::new("dots", &FILES);
+        assert_eq!(expand(&tree.0, ".*"), [".hidden"]);
+        assert_eq!(expand(&tree.0, "sub/.*.txt"), ["sub/.d.txt"]);
+        assert!(expand(&tree.0, "?hidden").is_empty());
+        assert_eq!(expand(&tree.0, "sub/*"), ["sub/c.txt", "sub/deep"]);
+    }
+
+    #[test]
+    fn trailing_slashes_keep_directories() {
+        let tree = Tree::new("slashes", &FILES);
+        assert_eq!(expand(&tree.0, "*/"), ["empty/", "sub/"]);
+        assert_eq!(expand(&tree.0, "sub/*/"), ["sub/deep/"]);
+    }
+
+    #[test]
+    fn double_stars_go_through_directories() {
+        let tree = Tree::new("stars", &FILES);
+        assert_eq!(expand(&tree.0, "**"), ["a.txt", "b.txt", "empty", "sub", "sub/c.txt", "sub/deep", "sub/deep/e.txt"]);
+        assert_eq!(expand(&tree.0, "**/*.txt"), ["a.txt", "b.txt", "sub/c.txt", "sub/deep/e.txt"]);
+        assert_eq!(expand(&tree.0, "**/"), ["empty/", "sub/", "sub/deep/"]);
+        assert_eq!(expand(&tree.0, "sub/**/e.txt"), ["sub/deep/e.txt"]);
+    }
+}
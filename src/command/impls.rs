@@ -1,64 +1,677 @@
-use crate::{format::Format, data::Input};
+use std::{process::Stdio, fs::{File, OpenOptions}, io::{self, Write}};
 
-use super::Command;
+use crate::{format::ast::{self, RedirectKind}, data::Input, system::duplicate};
+
+use super::{SYSTEM_COMMANDS, Command, Redirect, Pipeline, Stage, Execution, Stream, Streams, Job, ProcessGroup, expand::{expand, expand_string, expand_aliases}, interpreter::{call, run_compound}};
 
 impl Command {
-    pub fn from<'a>(format: &'a Format) -> Self {
-        Self {
-            name: format.get_name(),
-            args: format.get_args(),
-            options: format.get_options()
+    /// Expands the words of a parsed command. This happens right before the
+    /// command runs, so it sees the effects of everything that ran earlier.
+    pub fn from(command: &ast::SimpleCommand, input: &mut Input) -> Result<Self, String> {
+        let command = &expand_aliases(input, command)?;
+        input.take_substituted();
+        let mut assignments = vec![];
+        for assignment in &command.assignments {
+            assignments.push((assignment.name.clone(), expand_string(input, &assignment.value)?));
+        }
+
+        let mut words = vec![];
+        for word in &command.words {
+            words.extend(expand(input, word)?);
         }
+
+        let mut redirects = vec![];
+        for redirect in &command.redirects {
+            redirects.push(Redirect::from(redirect, input)?);
+        }
+
+        let mut words = words.into_iter();
+        Ok(Self {
+            assignments,
+            name: words.next().unwrap_or_default(),
+            args: words.collect(),
+            redirects,
+            status: input.take_substituted().unwrap_or(0)
+        })
+    }
+
+    // Builtins, functions and assignments, as opposed to programs.
+    fn in_shell(&self, input: &Input) -> bool {
+        let name = self.name.trim();
+        name.is_empty()
+            || (SYSTEM_COMMANDS.contains(&name) && (name != "env" || self.args.is_empty()))
+            || input.function(name).is_some()
+    }
+
+    fn text(&self) -> String {
+        let mut words = vec![];
+        for (name, value) in &self.assignments {
+            words.push(format!("{}={}", name, value));
+        }
+
+        if !self.name.is_empty() {
+            words.push(self.name.clone());
+        }
+
+        words.extend(self.args.iter().cloned());
+        for redirect in &self.redirects {
+            words.push(redirect.text());
+        }
+
+        words.join(" ")
+    }
+
+    /// Runs the command on its own, with `extra` arguments and variables
+    /// added, and returns what it wrote to standard output.
+    pub fn output(&self, extra: &[&str], variables: &[(&str, String)]) -> Option<String> {
+        let output = std::process::Command::new(&self.name)
+            .args(&self.args)
+            .args(extra)
+            .envs(self.assignments.iter().map(|(name, value)| (name.as_str(), value.as_str())))
+            .envs(variables.iter().map(|(name, value)| (*name, value.as_str())))
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()
+            .ok()?;
+
+        Some(String::from_utf8_lossy(&output.stdout).into_owned())
     }
 
     #[cfg(target_family = "windows")]
-    pub fn execute(&self, input: &mut Input) -> Option<std::process::Child> {
-        use crate::command::system::cd;
+    pub fn execute(&self, input: &mut Input, streams: Streams, _: &mut ProcessGroup) -> Execution {
+        use super::system::{cd, exit, export, unset, env, set, complete, source, alias, unalias, r#return, local, r#break, r#continue, r#let, echo};
+
+        let mut streams = match streams.redirect(&self.redirects) {
+            Some(streams) => streams,
+            None => return Execution::Finished(1)
+        };
 
-        use super::system::exit;
+        let name = self.name.trim();
+        if name.is_empty() {
+            for (name, value) in &self.assignments {
+                input.set_var(name, value.clone());
+            }
+
+            return Execution::Finished(self.status);
+        }
 
-        let name = self.name.clone();
-        let args = self.args.clone();
-        let options = self.options.clone();
+        // The job control builtins only exist on Unix, here those names are
+        // left to PowerShell like any other command.
+        let status = match name {
+            "exit" => Some(exit(input, self, &mut streams)),
+            "cd" => Some(cd(input, self, &mut streams)),
+            "export" => Some(export(input, self, &mut streams)),
+            "unset" => Some(unset(input, self, &mut streams)),
+            "env" if self.args.is_empty() => Some(env(input, self, &mut streams)),
+            "set" => Some(set(input, self, &mut streams)),
+            "complete" => Some(complete(input, self, &mut streams)),
+            "source" | "." => Some(source(input, self, &mut streams)),
+            "alias" => Some(alias(input, self, &mut streams)),
+            "unalias" => Some(unalias(input, self, &mut streams)),
+            "return" => Some(r#return(input, self, &mut streams)),
+            "local" => Some(local(input, self, &mut streams)),
+            "break" => Some(r#break(input, self, &mut streams)),
+            "continue" => Some(r#continue(input, self, &mut streams)),
+            "let" => Some(r#let(input, self, &mut streams)),
+            ":" => Some(0),
+            "echo" => Some(echo(input, self, &mut streams)),
+            _ => None
+        };
+
+        if let Some(status) = status {
+            return Execution::Finished(status);
+        }
 
-        let mut command =
-            std::process::Command::new("powershell");
-        
+        if let Some(function) = input.function(name).cloned() {
+            return Execution::Finished(call(input, &function, self, streams));
+        }
+
+        let mut errors = streams.stream(2);
+        let [stdin, stdout, stderr] = streams.into_stdio();
+        let mut command = std::process::Command::new("powershell");
         command
-            .stdin(std::process::Stdio::inherit())
-            .stdout(std::process::Stdio::inherit())
-            .stderr(std::process::Stdio::inherit())
-            .args(&["/C", name.as_str()]);
+            .stdin(stdin)
+            .stdout(stdout)
+            .stderr(stderr)
+            .args(["/C", name])
+            .args(&self.args)
+            .envs(self.assignments.iter().map(|(name, value)| (name, value)));
+
+        match command.spawn() {
+            Ok(child) => Execution::Spawned(child),
+            Err(err) => {
+                let _ = writeln!(errors.as_stderr(), "sks: {}: {}", name, err);
+
+                Execution::Finished(127)
+            }
+        }
+    }
+
+    #[cfg(target_family = "unix")]
+    pub fn execute(&self, input: &mut Input, streams: Streams, group: &mut ProcessGroup) -> Execution {
+        use std::os::unix::process::CommandExt;
+
+        use crate::system::find_executable;
+
+        use super::system::{cd, exit, export, unset, env, set, jobs, fg, bg, wait, kill, complete, source, alias, unalias, r#return, local, r#break, r#continue, r#let, echo, SYSTEM_COMMANDS};
+
+        // Redirections are set up even when there is nothing to run, so
+        // `> file` still creates the file and a bad target is reported.
+        let mut streams = match streams.redirect(&self.redirects) {
+            Some(streams) => streams,
+            None => return Execution::Finished(1)
+        };
+
+        let name = self.name.trim();
+        if name.is_empty() {
+            for (name, value) in &self.assignments {
+                input.set_var(name, value.clone());
+            }
 
-        for arg in args {
-            command.arg(arg);
+            return Execution::Finished(self.status);
         }
 
-        for option in options {
-            command.args([option.0, option.1]);
+        // `env` with arguments runs a program, which the real `env` does.
+        if SYSTEM_COMMANDS.contains(&name) && (name != "env" || self.args.is_empty()) {
+            return Execution::Finished(match name {
+                "exit" => exit(input, self, &mut streams),
+                "cd" => cd(input, self, &mut streams),
+                "export" => export(input, self, &mut streams),
+                "unset" => unset(input, self, &mut streams),
+                "env" => env(input, self, &mut streams),
+                "set" => set(input, self, &mut streams),
+                "jobs" => jobs(input, self, &mut streams),
+                "fg" => fg(input, self, &mut streams),
+                "bg" => bg(input, self, &mut streams),
+                "wait" => wait(input, self, &mut streams),
+                "kill" => kill(input, self, &mut streams),
+                "complete" => complete(input, self, &mut streams),
+                "source" | "." => source(input, self, &mut streams),
+                "alias" => alias(input, self, &mut streams),
+                "unalias" => unalias(input, self, &mut streams),
+                "return" => r#return(input, self, &mut streams),
+                "local" => local(input, self, &mut streams),
+                "break" => r#break(input, self, &mut streams),
+                "continue" => r#continue(input, self, &mut streams),
+                "let" => r#let(input, self, &mut streams),
+                // `:` does nothing and succeeds, as in `while :; do`.
+                ":" => 0,
+                "echo" => echo(input, self, &mut streams),
+                _ => 0
+            });
         }
 
-        let result = match command.spawn() {
+        if let Some(function) = input.function(name).cloned() {
+            return Execution::Finished(call(input, &function, self, streams));
+        }
+
+        let program = match find_executable(name) {
+            Some(program) => program,
+            None => {
+                let _ = writeln!(streams.stderr(), "sks: command not found: {}", name);
+
+                return Execution::Finished(127);
+            }
+        };
+
+        let mut errors = streams.stream(2);
+        let [stdin, stdout, stderr] = streams.into_stdio();
+        let mut command = std::process::Command::new(program);
+        command
+            .arg0(name)
+            .stdin(stdin)
+            .stdout(stdout)
+            .stderr(stderr)
+            .args(&self.args)
+            .envs(self.assignments.iter().map(|(name, value)| (name, value)));
+
+        // Under job control every job gets a process group of its own, and a
+        // foreground job takes the terminal before it can read from it.
+        if let Some(terminal) = input.terminal() {
+            let fd = group.foreground.then(|| terminal.fd());
+            command.process_group(group.pgid.unwrap_or(0) as i32);
+            unsafe {
+                command.pre_exec(move || {
+                    if let Some(fd) = fd {
+                        libc::tcsetpgrp(fd, libc::getpgrp());
+                    }
+
+                    libc::signal(libc::SIGTTOU, libc::SIG_DFL);
+                    libc::signal(libc::SIGTTIN, libc::SIG_DFL);
+                    Ok(())
+                });
+            }
+        }
+
+        match command.spawn() {
             Ok(child) => {
-                Some(child)
+                if input.terminal().is_some() && group.pgid.is_none() {
+                    group.pgid = Some(child.id());
+                }
+
+                Execution::Spawned(child)
             },
             Err(err) => {
-                println!("{}", err);
+                let _ = writeln!(errors.as_stderr(), "sks: {}: {}", name, err);
 
-                None
+                Execution::Finished(126)
             }
+        }
+    }
+}
+
+impl Redirect {
+    pub fn from(redirect: &ast::Redirect, input: &mut Input) -> Result<Self, String> {
+        Ok(Self {
+            fd: redirect.fd,
+            kind: redirect.kind,
+            target: expand_string(input, &redirect.target)?
+        })
+    }
+
+    fn open(&self) -> Result<File, String> {
+        let mut options = OpenOptions::new();
+        match self.kind {
+            RedirectKind::Input => options.read(true),
+            RedirectKind::Output | RedirectKind::OutputAll => options.write(true).create(true).truncate(true),
+            RedirectKind::Append | RedirectKind::AppendAll => options.append(true).create(true),
+            RedirectKind::Duplicate => unreachable!("`>&` does not open a file")
         };
 
-        if let Some(name) = self.name.trim().split_whitespace().nth(0) {
-            if super::system::SYSTEM_COMMANDS.contains(&name) {
-                match name {
-                    "exit" => exit(input),
-                    "cd" => cd(input, self),
-                    _ => ()
+        options
+            .open(&self.target)
+            .map_err(|err| format!("{}: {}", self.target, err))
+    }
+
+    fn text(&self) -> String {
+        let operator = match self.kind {
+            RedirectKind::Input => "<",
+            RedirectKind::Output => ">",
+            RedirectKind::Append => ">>",
+            RedirectKind::Duplicate => ">&",
+            RedirectKind::OutputAll => "&>",
+            RedirectKind::AppendAll => "&>>"
+        };
+
+        let default = match self.kind {
+            RedirectKind::Input => 0,
+            RedirectKind::OutputAll | RedirectKind::AppendAll => self.fd,
+            _ => 1
+        };
+
+        if self.fd == default {
+            format!("{}{}", operator, self.target)
+        } else {
+            format!("{}{}{}", self.fd, operator, self.target)
+        }
+    }
+}
+
+impl Pipeline {
+    pub fn from(pipeline: &ast::Pipeline, input: &mut Input) -> Result<Self, String> {
+        let mut commands = vec![];
+        for command in &pipeline.commands {
+            commands.push(match command {
+                ast::Command::Simple(command) => Stage::Simple(Command::from(command, input)?),
+                ast::Command::Compound(compound, redirects) => {
+                    let redirects = redirects
+                        .iter()
+                        .map(|redirect| Redirect::from(redirect, input))
+                        .collect::<Result<_, _>>()?;
+
+                    Stage::Compound(compound.clone(), redirects)
+                },
+                ast::Command::Function(function) => Stage::Function(function.clone())
+            });
+        }
+
+        Ok(Self {
+            commands
+        })
+    }
+
+    /// Starts every stage at once, each one reading what the previous stage
+    /// writes. The processes of the job are in stage order, so the last one
+    /// decides the status of the whole pipeline.
+    ///
+    /// A stage the shell runs itself gets a copy of the shell to run in,
+    /// except for the last one, so it neither holds up the stages after it
+    /// nor changes the shell. Windows has no such copies: there these stages
+    /// run once the programs of the pipeline have started.
+    pub fn execute(&self, input: &mut Input, foreground: bool) -> Job {
+        let mut executions = vec![];
+        let mut deferred = vec![];
+        let mut reader = None;
+        let mut group = ProcessGroup {
+            pgid: None,
+            foreground
+        };
+
+        for (idx, command) in self.commands.iter().enumerate() {
+            let last = idx + 1 == self.commands.len();
+            let stdin = match reader.take() {
+                Some(reader) => Stream::Reader(reader),
+                None => input.stream(0)
+            };
+
+            let stdout = if last {
+                input.stream(1)
+            } else {
+                match io::pipe() {
+                    Ok((next, writer)) => {
+                        reader = Some(next);
+                        Stream::Writer(writer)
+                    },
+                    Err(err) => {
+                        input.error(format!("cannot create pipe: {}", err));
+                        executions.push(Execution::Finished(1));
+
+                        break;
+                    }
                 }
+            };
+
+            let streams = Streams {
+                fds: [stdin, stdout, input.stream(2)]
+            };
+
+            if !command.in_shell(input) {
+                executions.push(command.execute(input, streams, &mut group));
+                continue;
             }
+
+            #[cfg(target_family = "unix")]
+            if !last {
+                use crate::system::fork;
+
+                executions.push(match fork() {
+                    Ok(Some(pid)) => {
+                        if input.terminal().is_some() {
+                            unsafe {
+                                libc::setpgid(pid as libc::pid_t, group.pgid.unwrap_or(pid) as libc::pid_t);
+                            }
+
+                            group.pgid.get_or_insert(pid);
+                        }
+
+                        Execution::Forked(pid)
+                    },
+                    Ok(None) => {
+                        // The reading end of the next pipe is not ours to
+                        // keep open.
+                        drop(reader);
+                        command.execute_forked(input, streams, &group)
+                    },
+                    Err(err) => {
+                        input.error(format!("cannot fork: {}", err));
+                        Execution::Finished(1)
+                    }
+                });
+
+                continue;
+            }
+
+            deferred.push((executions.len(), streams));
+            executions.push(Execution::Finished(0));
+        }
+
+        for (idx, streams) in deferred {
+            let command = &self.commands[idx];
+            executions[idx] = if idx + 1 == self.commands.len() {
+                command.execute(input, streams, &mut group)
+            } else {
+                let subshell = input.enter_subshell();
+                let execution = command.execute(input, streams, &mut group);
+                input.leave_subshell(subshell);
+
+                execution
+            };
+        }
+
+        Job::new(executions, group.pgid, self.text())
+    }
+
+    /// The pipeline as it is shown in job listings.
+    pub fn text(&self) -> String {
+        self.commands
+            .iter()
+            .map(Stage::text)
+            .collect::<Vec<_>>()
+            .join(" | ")
+    }
+}
+
+impl Stage {
+    fn execute(&self, input: &mut Input, streams: Streams, group: &mut ProcessGroup) -> Execution {
+        match self {
+            Stage::Simple(command) => command.execute(input, streams, group),
+            Stage::Compound(compound, redirects) => match streams.redirect(redirects) {
+                Some(streams) => Execution::Finished(input.with_streams(streams, |input| run_compound(input, compound))),
+                None => Execution::Finished(1)
+            },
+            Stage::Function(function) => {
+                input.set_function(function.clone());
+
+                Execution::Finished(0)
+            }
+        }
+    }
+
+    fn in_shell(&self, input: &Input) -> bool {
+        match self {
+            Stage::Simple(command) => command.in_shell(input),
+            Stage::Compound(..) | Stage::Function(_) => true
+        }
+    }
+
+    // The forked copy of the shell leaves the terminal to the shell, after
+    // joining the process group of the pipeline, and ends with the stage.
+    #[cfg(target_family = "unix")]
+    fn execute_forked(&self, input: &mut Input, streams: Streams, group: &ProcessGroup) -> ! {
+        if let Some(terminal) = input.terminal() {
+            unsafe {
+                libc::setpgid(0, group.pgid.unwrap_or(0) as libc::pid_t);
+                if group.foreground {
+                    libc::tcsetpgrp(terminal.fd(), libc::getpgrp());
+                }
+            }
+        }
+
+        input.forked();
+        let mut group = ProcessGroup {
+            pgid: None,
+            foreground: false
+        };
+
+        let status = match self.execute(input, streams, &mut group) {
+            Execution::Finished(status) => status,
+            execution => input.foreground(Job::new(vec![execution], None, self.text()))
+        };
+
+        input.exit(status)
+    }
+
+    fn text(&self) -> String {
+        match self {
+            Stage::Simple(command) => command.text(),
+            Stage::Compound(compound, _) => compound_text(compound),
+            Stage::Function(function) => format!("{}() {}", function.name, compound_text(&function.body))
+        }
+    }
+}
+
+fn compound_text(compound: &ast::CompoundCommand) -> String {
+    match compound {
+        ast::CompoundCommand::Group(_) => "{ ...; }".into(),
+        ast::CompoundCommand::If { .. } => "if ...; fi".into(),
+        ast::CompoundCommand::While { until: false, .. } => "while ...; done".into(),
+        ast::CompoundCommand::While { until: true, .. } => "until ...; done".into(),
+        ast::CompoundCommand::For { name, .. } => format!("for {}; do ...; done", name),
+        ast::CompoundCommand::Case { word, .. } => format!("case {} in ...; esac", word.text())
+    }
+}
+
+impl Stream {
+    pub fn try_clone(&self) -> io::Result<Self> {
+        Ok(match self {
+            Stream::Inherit => Stream::Inherit,
+            Stream::File(file) => Stream::File(file.try_clone()?),
+            Stream::Reader(reader) => Stream::Reader(reader.try_clone()?),
+            Stream::Writer(writer) => Stream::Writer(writer.try_clone()?)
+        })
+    }
+}
+
+impl Stream {
+    // An inherited standard error has to go to our own standard error, not
+    // to the standard output `Stream::Inherit` writes to.
+    pub fn as_stderr(&mut self) -> Box<dyn Write + '_> {
+        match self {
+            Stream::Inherit => Box::new(io::stderr()),
+            stream => Box::new(stream)
+        }
+    }
+}
+
+impl From<Stream> for Stdio {
+    fn from(stream: Stream) -> Self {
+        match stream {
+            Stream::Inherit => Stdio::inherit(),
+            Stream::File(file) => file.into(),
+            Stream::Reader(reader) => reader.into(),
+            Stream::Writer(writer) => writer.into()
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Stream::Inherit => io::stdout().write(buf),
+            Stream::File(file) => file.write(buf),
+            Stream::Writer(writer) => writer.write(buf),
+            Stream::Reader(_) => Err(io::Error::other("stream is not writable"))
         }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Stream::Inherit => io::stdout().flush(),
+            Stream::File(file) => file.flush(),
+            Stream::Writer(writer) => writer.flush(),
+            Stream::Reader(_) => Ok(())
+        }
+    }
+}
+
+impl Streams {
+    pub fn stdout(&mut self) -> &mut Stream {
+        &mut self.fds[1]
+    }
+
+    pub fn stderr(&mut self) -> Box<dyn Write + '_> {
+        self.fds[2].as_stderr()
+    }
+
+    /// Applies the redirections from left to right, so `> log 2>&1` sends
+    /// both outputs to `log` while `2>&1 > log` only moves standard output.
+    /// A redirection that fails is reported on standard error as redirected
+    /// up to there, and leaves nothing to run.
+    pub fn redirect(mut self, redirects: &[Redirect]) -> Option<Self> {
+        for redirect in redirects {
+            if let Err(err) = self.apply(redirect) {
+                let _ = writeln!(self.stderr(), "sks: {}", err);
+                return None;
+            }
+        }
+
+        Some(self)
+    }
+
+    fn apply(&mut self, redirect: &Redirect) -> Result<(), String> {
+        let fd = redirect.fd as usize;
+        if fd >= self.fds.len() {
+            return Err(format!("{}: unsupported file descriptor", redirect.fd));
+        }
+
+        match redirect.kind {
+            RedirectKind::Duplicate => {
+                let source = match redirect.target.parse::<usize>() {
+                    Ok(source) if source < self.fds.len() => source,
+                    _ => return Err(format!("{}: bad file descriptor", redirect.target))
+                };
+
+                // `Stream::Inherit` means the shell's own descriptor of the
+                // same number, so putting it behind another one takes a real
+                // copy of it.
+                let stream = match &self.fds[source] {
+                    Stream::Inherit if source != fd => duplicate(source).map(Stream::File),
+                    stream => stream.try_clone()
+                };
+
+                self.fds[fd] = stream.map_err(|err| format!("{}: {}", redirect.target, err))?;
+            },
+            RedirectKind::OutputAll | RedirectKind::AppendAll => {
+                let file = redirect.open()?;
+                let clone = file
+                    .try_clone()
+                    .map_err(|err| format!("{}: {}", redirect.target, err))?;
+
+                self.fds[1] = Stream::File(file);
+                self.fds[2] = Stream::File(clone);
+            },
+            _ => self.fds[fd] = Stream::File(redirect.open()?)
+        }
+
+        Ok(())
+    }
+
+    /// A copy of the stream behind descriptor `fd`, for the commands a
+    /// function or group runs with these streams.
+    pub fn stream(&self, fd: usize) -> Stream {
+        self.fds[fd].try_clone().unwrap_or(Stream::Inherit)
+    }
+
+    pub fn into_stdio(self) -> [Stdio; 3] {
+        self.fds.map(Stdio::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::mpsc, thread, time::Duration};
+
+    use crate::{data::Input, format::Format, command::interpreter::capture};
+
+    // What `script` writes, as `$(...)` gets it, or `None` when it takes
+    // too long.
+    fn output(script: &'static str) -> Option<String> {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let mut input = Input::script(vec!["sks".into()]);
+            let program = Format::from(script).into_program().unwrap();
+            let _ = sender.send(capture(&mut input, &program));
+        });
+
+        receiver.recv_timeout(Duration::from_secs(10)).ok()?.ok()
+    }
+
+    #[test]
+    #[cfg(target_family = "unix")]
+    fn in_shell_stages_do_not_block() {
+        assert_eq!(output("g() { seq 1 50000; }; g | wc -l").as_deref(), Some("50000"));
+        assert_eq!(output("f() { cat; }; seq 1 100000 | f | wc -l").as_deref(), Some("100000"));
+        assert_eq!(output("seq 1 100000 | { cat; } | tail -1").as_deref(), Some("100000"));
+        assert_eq!(output("g() { seq 1 70000; }; f() { cat; }; g | f | f | wc -l").as_deref(), Some("70000"));
+    }
+
+    #[test]
+    #[cfg(target_family = "unix")]
+    fn in_shell_stages_leave_the_shell_alone() {
+        assert_eq!(output("exit 2 | cat; echo $?").as_deref(), Some("0"));
+        assert_eq!(output("x=1 | cat; echo \"[$x]\"").as_deref(), Some("[]"));
 
-        result
+        let directory = std::env::current_dir().unwrap();
+        assert_eq!(output("cd / | cat; pwd"), Some(directory.display().to_string()));
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,301 @@
+use std::io::Read;
+
+use crate::{data::Input, format::ast::{Program, List, AndOr, Connector, Pipeline, CompoundCommand, CaseItem, Function, Word}};
+
+use super::{Command, Stream, Streams, Flow, glob, expand::{expand, expand_string, expand_pattern}};
+
+// Deep enough for any sane recursion, shallow enough for the stack.
+const MAX_DEPTH: usize = 256;
+
+pub fn run(input: &mut Input, program: &Program) -> i32 {
+    run_list(input, &program.list)
+}
+
+fn run_list(input: &mut Input, list: &List) -> i32 {
+    let mut status = input.status();
+    for and_or in &list.items {
+        status = run_and_or(input, and_or);
+        input.check_interrupt();
+        if input.interrupted() || input.flow().is_some() {
+            break;
+        }
+    }
+
+    status
+}
+
+fn run_and_or(input: &mut Input, and_or: &AndOr) -> i32 {
+    if and_or.background {
+        return run_background(input, and_or);
+    }
+
+    let mut status = run_pipeline(input, &and_or.first);
+    let mut last = and_or.rest.is_empty();
+    for (idx, (connector, pipeline)) in and_or.rest.iter().enumerate() {
+        let proceed = match connector {
+            Connector::And => status == 0,
+            Connector::Or => status != 0
+        };
+
+        if input.interrupted() || input.flow().is_some() {
+            break;
+        }
+
+        if proceed {
+            status = run_pipeline(input, pipeline);
+            last = idx + 1 == and_or.rest.len();
+        }
+    }
+
+    // Like `set -e` elsewhere, a failure on the left of `&&` or `||` is
+    // being tested and does not end the script.
+    if status != 0 && last && input.errexit() && !input.condition() && !input.interrupted() && input.flow().is_none() {
+        return input.finish(status);
+    }
+
+    status
+}
+
+pub fn run_compound(input: &mut Input, compound: &CompoundCommand) -> i32 {
+    match compound {
+        CompoundCommand::Group(list) => run_list(input, list),
+        CompoundCommand::If { branches, otherwise } => run_if(input, branches, otherwise.as_ref()),
+        CompoundCommand::While { condition, body, until } => run_while(input, condition, body, *until),
+        CompoundCommand::For { name, words, body } => run_for(input, name, words.as_deref(), body),
+        CompoundCommand::Case { word, items } => run_case(input, word, items)
+    }
+}
+
+fn run_condition(input: &mut Input, list: &List) -> i32 {
+    let previous = input.condition();
+    input.set_condition(true);
+    let status = run_list(input, list);
+    input.set_condition(previous);
+
+    status
+}
+
+fn run_if(input: &mut Input, branches: &[(List, List)], otherwise: Option<&List>) -> i32 {
+    for (condition, body) in branches {
+        let status = run_condition(input, condition);
+        if input.interrupted() || input.flow().is_some() {
+            return status;
+        }
+
+        if status == 0 {
+            return run_list(input, body);
+        }
+    }
+
+    match otherwise {
+        Some(list) => run_list(input, list),
+        None => 0
+    }
+}
+
+fn run_while(input: &mut Input, condition: &List, body: &List, until: bool) -> i32 {
+    let mut status = 0;
+    input.enter_loop();
+    loop {
+        let tested = run_condition(input, condition);
+        if !next_round(input) || (tested == 0) == until {
+            break;
+        }
+
+        status = run_list(input, body);
+        if !next_round(input) {
+            break;
+        }
+    }
+
+    input.leave_loop();
+    status
+}
+
+fn run_for(input: &mut Input, name: &str, words: Option<&[Word]>, body: &List) -> i32 {
+    let values = match words {
+        Some(words) => {
+            let mut values = vec![];
+            for word in words {
+                match expand(input, word) {
+                    Ok(fields) => values.extend(fields),
+                    Err(err) => {
+                        input.error(err);
+                        return 1;
+                    }
+                }
+            }
+
+            values
+        },
+        None => input.arguments()[1..].to_vec()
+    };
+
+    let mut status = 0;
+    input.enter_loop();
+    for value in values {
+        input.set_var(name, value);
+        status = run_list(input, body);
+        if !next_round(input) {
+            break;
+        }
+    }
+
+    input.leave_loop();
+    status
+}
+
+// Runs the first item with a pattern that matches the word.
+fn run_case(input: &mut Input, word: &Word, items: &[CaseItem]) -> i32 {
+    let subject = match expand_string(input, word) {
+        Ok(subject) => subject,
+        Err(err) => {
+            input.error(err);
+            return 1;
+        }
+    };
+
+    for item in items {
+        for pattern in &item.patterns {
+            let pattern = match expand_pattern(input, pattern) {
+                Ok(pattern) => pattern,
+                Err(err) => {
+                    input.error(err);
+                    return 1;
+                }
+            };
+
+            if glob::matches(&pattern, &subject) {
+                return match item.body.items.is_empty() {
+                    true => 0,
+                    false => run_list(input, &item.body)
+                };
+            }
+        }
+    }
+
+    0
+}
+
+// Settles a `break` or `continue` that is aimed at the loop running it
+// and tells whether the loop goes on with its next round.
+fn next_round(input: &mut Input) -> bool {
+    input.check_interrupt();
+    match input.flow() {
+        Some(Flow::Break(count)) => {
+            input.set_flow((count > 1).then(|| Flow::Break(count - 1)));
+            false
+        },
+        Some(Flow::Continue(count)) if count > 1 => {
+            input.set_flow(Some(Flow::Continue(count - 1)));
+            false
+        },
+        Some(Flow::Continue(_)) => {
+            input.set_flow(None);
+            true
+        },
+        Some(Flow::Return | Flow::Exit) => false,
+        None => !input.interrupted()
+    }
+}
+
+/// Runs a function for `command`, whose arguments become the positional
+/// parameters and whose assignments become local variables of the call.
+pub fn call(input: &mut Input, function: &Function, command: &Command, streams: Streams) -> i32 {
+    if input.depth() >= MAX_DEPTH {
+        input.error(format!("{}: maximum function nesting level exceeded ({})", function.name, MAX_DEPTH));
+        return 1;
+    }
+
+    input.enter_function(command.args.clone());
+    for (name, value) in &command.assignments {
+        input.set_local(name, Some(value.clone()));
+    }
+
+    let status = input.with_streams(streams, |input| run_compound(input, &function.body));
+    input.leave_function();
+
+    match input.flow() {
+        Some(Flow::Return) => {
+            input.set_flow(None);
+            input.status()
+        },
+        _ => status
+    }
+}
+
+/// Runs `program` for a command substitution and returns what it wrote to
+/// its standard output, without the line breaks at the end. Like a subshell,
+/// what it does to variables, functions and the directory does not stay.
+pub fn capture(input: &mut Input, program: &Program) -> Result<String, String> {
+    let (mut reader, writer) = std::io::pipe().map_err(|err| err.to_string())?;
+
+    // Read while the commands are still writing, or a full pipe would
+    // block them for good.
+    let output = std::thread::spawn(move || {
+        let mut output = vec![];
+        reader.read_to_end(&mut output).map(|_| output)
+    });
+
+    let streams = Streams {
+        fds: [input.stream(0), Stream::Writer(writer), input.stream(2)]
+    };
+
+    let subshell = input.enter_subshell();
+    let status = input.with_streams(streams, |input| run(input, program));
+    input.leave_subshell(subshell);
+    input.set_substituted(status);
+
+    let output = output
+        .join()
+        .unwrap_or_else(|_| Ok(vec![]))
+        .map_err(|err| err.to_string())?;
+
+    Ok(String::from_utf8_lossy(&output).trim_end_matches('\n').into())
+}
+
+// Only a single pipeline can go to the background: its processes are all
+// that is left to track once the shell moves on, while `&&` and `||` would
+// need the shell itself to keep deciding in the background.
+fn run_background(input: &mut Input, and_or: &AndOr) -> i32 {
+    if !and_or.rest.is_empty() {
+        input.error("only a single pipeline can run in the background");
+        input.set_status(1);
+
+        return 1;
+    }
+
+    let status = match super::Pipeline::from(&and_or.first, input) {
+        Ok(pipeline) => {
+            let job = pipeline.execute(input, false);
+            input.background(job);
+
+            0
+        },
+        Err(err) => {
+            input.error(err);
+
+            1
+        }
+    };
+
+    input.set_status(status);
+    status
+}
+
+fn run_pipeline(input: &mut Input, pipeline: &Pipeline) -> i32 {
+    let status = match super::Pipeline::from(pipeline, input) {
+        Ok(pipeline) => {
+            let job = pipeline.execute(input, true);
+            input.foreground(job)
+        },
+        Err(err) => {
+            input.error(err);
+
+            1
+        }
+    };
+
+    input.set_status(status);
+    status
+}
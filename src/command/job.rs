@@ -0,0 +1,276 @@
+use std::thread::spawn;
+
+use crossbeam_channel::Sender;
+
+use super::{Job, Process, JobState, Execution};
+
+impl Job {
+    pub fn new(executions: Vec<Execution>, pgid: Option<u32>, text: String) -> Self {
+        let processes = executions
+            .into_iter()
+            .map(|execution| match execution {
+                Execution::Spawned(child) => Process {
+                    pid: Some(child.id()),
+                    child: Some(child),
+                    status: None,
+                    signal: None,
+                    stopped: None
+                },
+                #[cfg(target_family = "unix")]
+                Execution::Forked(pid) => Process {
+                    pid: Some(pid),
+                    child: None,
+                    status: None,
+                    signal: None,
+                    stopped: None
+                },
+                Execution::Finished(status) => Process {
+                    pid: None,
+                    child: None,
+                    status: Some(status),
+                    signal: None,
+                    stopped: None
+                }
+            })
+            .collect();
+
+        let mut job = Self {
+            id: 0,
+            pgid,
+            processes,
+            text,
+            state: JobState::Running
+        };
+
+        job.update();
+        job
+    }
+
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    pub fn set_id(&mut self, id: usize) {
+        self.id = id;
+    }
+
+    pub fn pgid(&self) -> Option<u32> {
+        self.pgid
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn state(&self) -> JobState {
+        self.state
+    }
+
+    pub fn pids(&self) -> Vec<u32> {
+        self.processes
+            .iter()
+            .filter_map(|process| process.pid)
+            .collect()
+    }
+
+    /// The status `$?` takes for this job: the status of the last process
+    /// once everything finished, or `128 + signal` for a stopped job.
+    pub fn status(&self) -> i32 {
+        match self.state {
+            JobState::Running => 0,
+            JobState::Stopped => 128 + self.processes
+                .iter()
+                .find_map(|process| process.stopped)
+                .unwrap_or(0),
+            JobState::Done(status) => status
+        }
+    }
+
+    /// Collects every state change of the processes without blocking.
+    #[cfg(target_family = "unix")]
+    pub fn poll(&mut self) {
+        use crate::system::{wait_process, ProcessChange};
+
+        for idx in 0..self.processes.len() {
+            let pid = match (self.processes[idx].pid, self.processes[idx].status) {
+                (Some(pid), None) => pid,
+                _ => continue
+            };
+
+            let process = &mut self.processes[idx];
+            loop {
+                match wait_process(pid, false) {
+                    Ok(Some(ProcessChange::Exited(status))) => {
+                        process.status = Some(status);
+                        break;
+                    },
+                    Ok(Some(ProcessChange::Signaled(signal))) => {
+                        process.status = Some(128 + signal);
+                        process.signal = Some(signal);
+                        break;
+                    },
+                    Ok(Some(ProcessChange::Stopped(signal))) => process.stopped = Some(signal),
+                    Ok(Some(ProcessChange::Continued)) => process.stopped = None,
+                    Ok(None) => break,
+                    // Somebody else reaped it, there is no status left to get.
+                    Err(_) => {
+                        process.status = Some(1);
+                        break;
+                    }
+                }
+            }
+        }
+
+        self.update();
+    }
+
+    #[cfg(target_family = "windows")]
+    pub fn poll(&mut self) {
+        use crate::system::exit_code;
+
+        for process in &mut self.processes {
+            if let (Some(child), None) = (&mut process.child, process.status) {
+                match child.try_wait() {
+                    Ok(Some(status)) => process.status = Some(exit_code(status)),
+                    Ok(None) => (),
+                    Err(_) => process.status = Some(1)
+                }
+            }
+        }
+
+        self.update();
+    }
+
+    /// Starts a thread for every live process that blocks until the process
+    /// has a state change to report, and then sends its index. Collecting
+    /// the change is left to `changed`, so nothing is lost when nobody waits
+    /// for the job anymore. Each thread reports once and `changed` starts a
+    /// new one for a process that keeps running.
+    pub fn watch(&self, sender: &Sender<usize>) {
+        for idx in 0..self.processes.len() {
+            self.watch_process(idx, sender);
+        }
+    }
+
+    pub fn changed(&mut self, idx: usize, sender: &Sender<usize>) {
+        self.poll();
+        self.watch_process(idx, sender);
+    }
+
+    fn watch_process(&self, idx: usize, sender: &Sender<usize>) {
+        use crate::system::watch_process;
+
+        let process = &self.processes[idx];
+        if process.status.is_some() || process.stopped.is_some() {
+            return;
+        }
+
+        #[cfg(target_family = "unix")]
+        let target = process.pid;
+        #[cfg(target_family = "windows")]
+        let target = process.child.as_ref().map(|child| {
+            use std::os::windows::io::AsRawHandle;
+
+            child.as_raw_handle() as usize
+        });
+
+        if let Some(target) = target {
+            let sender = sender.clone();
+            spawn(move || {
+                let _ = watch_process(target);
+                let _ = sender.send(idx);
+            });
+        }
+    }
+
+    /// Sends `signal` to the whole job: to its process group when it has
+    /// one, otherwise to each of its processes that is still alive.
+    #[cfg(target_family = "unix")]
+    pub fn signal(&self, signal: i32) -> std::io::Result<()> {
+        use crate::system::send_signal;
+
+        if let Some(pgid) = self.pgid {
+            return send_signal(-(pgid as i32), signal);
+        }
+
+        for process in &self.processes {
+            if let (Some(pid), None) = (process.pid, process.status) {
+                send_signal(pid as i32, signal)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    #[cfg(target_family = "unix")]
+    pub fn resume(&mut self) -> std::io::Result<()> {
+        if self.state == JobState::Stopped {
+            self.signal(libc::SIGCONT)?;
+        }
+
+        for process in &mut self.processes {
+            process.stopped = None;
+        }
+
+        self.update();
+        Ok(())
+    }
+
+    /// Whether Ctrl-C ended the job, as opposed to a process that merely
+    /// exited with the status that stands for it.
+    #[cfg(target_family = "unix")]
+    pub fn interrupted(&self) -> bool {
+        self.processes.iter().any(|process| process.signal == Some(libc::SIGINT))
+    }
+
+    // Ctrl-C reaches the shell itself as a key there, which `Input::wait`
+    // already takes care of.
+    #[cfg(target_family = "windows")]
+    pub fn interrupted(&self) -> bool {
+        false
+    }
+
+    /// Passes Ctrl-C on to the job.
+    #[cfg(target_family = "unix")]
+    pub fn interrupt(&self) {
+        let _ = self.signal(libc::SIGINT);
+    }
+
+    // Every process attached to the console gets Ctrl-C from Windows itself.
+    #[cfg(target_family = "windows")]
+    pub fn interrupt(&self) {
+
+    }
+
+    /// One line of `jobs` output, `marker` being `+` for the current job,
+    /// `-` for the previous one and a blank for the others.
+    pub fn line(&self, marker: char) -> String {
+        let state = match self.state {
+            JobState::Running => "Running".to_string(),
+            JobState::Stopped => "Stopped".to_string(),
+            JobState::Done(0) => "Done".to_string(),
+            JobState::Done(status) => format!("Exit {}", status)
+        };
+
+        format!("[{}]{}  {:<24}{}", self.id, marker, state, self.text)
+    }
+
+    /// The marker of the job at `idx` in a table of `count` jobs.
+    pub fn marker(idx: usize, count: usize) -> char {
+        match count - idx {
+            1 => '+',
+            2 => '-',
+            _ => ' '
+        }
+    }
+
+    fn update(&mut self) {
+        self.state = if self.processes.iter().all(|process| process.status.is_some()) {
+            JobState::Done(self.processes.last().and_then(|process| process.status).unwrap_or(0))
+        } else if self.processes.iter().any(|process| process.status.is_none() && process.stopped.is_none()) {
+            JobState::Running
+        } else {
+            JobState::Stopped
+        };
+    }
+}
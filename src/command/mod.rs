@@ -1,11 +1,119 @@
-use crate::data::KeyBoardSignalGenerator;
-
 mod impls;
 mod system;
+mod expand;
+mod arithmetic;
+mod job;
+pub mod glob;
+pub mod interpreter;
+
+pub use system::{SYSTEM_COMMANDS, SYSTEM_OPTIONS};
+
+use std::{process::Child, fs::File, io::{PipeReader, PipeWriter}};
+
+use crate::format::ast::{self, RedirectKind};
 
 #[derive(Debug, Clone)]
 pub struct Command {
+    assignments: Vec<(String, String)>,
     name: String,
     args: Vec<String>,
-    options: Vec<(String, String)>
-}
\ No newline at end of file
+    redirects: Vec<Redirect>,
+    // What a command of only assignments finishes with: the status of its
+    // last command substitution.
+    status: i32
+}
+
+#[derive(Debug, Clone)]
+pub struct Redirect {
+    fd: u32,
+    kind: RedirectKind,
+    target: String
+}
+
+#[derive(Debug, Clone)]
+pub struct Pipeline {
+    commands: Vec<Stage>
+}
+
+/// One command of a pipeline. Compound commands and function definitions
+/// are run by the shell itself, with the streams of their stage, in a copy
+/// of the shell unless they are the last stage.
+#[derive(Debug, Clone)]
+pub enum Stage {
+    Simple(Command),
+    Compound(ast::CompoundCommand, Vec<Redirect>),
+    Function(ast::Function)
+}
+
+/// Where running a list stops early, besides an interrupt: `break n` and
+/// `continue n` leave or go on with the `n`th enclosing loop, and `exit` in
+/// a command substitution ends only that.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flow {
+    Exit,
+    Return,
+    Break(usize),
+    Continue(usize)
+}
+
+#[derive(Debug)]
+pub enum Execution {
+    Spawned(Child),
+    // A copy of the shell running a stage of a pipeline.
+    #[cfg(target_family = "unix")]
+    Forked(u32),
+    Finished(i32)
+}
+
+/// The process group the commands of a pipeline are started in. The first
+/// spawned process leads the group and the others join it.
+#[derive(Debug, Clone, Copy)]
+pub struct ProcessGroup {
+    pgid: Option<u32>,
+    foreground: bool
+}
+
+/// A pipeline the shell has started, tracked until every one of its
+/// processes has been reaped.
+#[derive(Debug)]
+pub struct Job {
+    id: usize,
+    pgid: Option<u32>,
+    processes: Vec<Process>,
+    text: String,
+    state: JobState
+}
+
+#[derive(Debug)]
+pub struct Process {
+    pid: Option<u32>,
+    // Only Windows waits through the child, Unix goes by the pid.
+    #[cfg_attr(target_family = "unix", allow(dead_code))]
+    child: Option<Child>,
+    status: Option<i32>,
+    // The signal that ended the process, if one did.
+    signal: Option<i32>,
+    stopped: Option<i32>
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobState {
+    Running,
+    Stopped,
+    Done(i32)
+}
+
+/// Where one of the standard file descriptors of a command points to.
+#[derive(Debug)]
+pub enum Stream {
+    Inherit,
+    File(File),
+    Reader(PipeReader),
+    Writer(PipeWriter)
+}
+
+/// Standard input, output and error of a command, indexed by descriptor.
+#[derive(Debug)]
+pub struct Streams {
+    fds: [Stream; 3]
+}
@@ -1,25 +1,839 @@
-use std::{path::PathBuf, env::{set_current_dir, current_dir}};
+use std::{env::{set_current_dir, current_dir}, io::Write, collections::BTreeMap, path::Path};
 
-use crate::data::Input;
+use crate::{data::{Input, CompletionSpec}, format::ast::is_name};
 
-use super::Command;
+use super::{Command, Streams, Job, JobState, Flow, expand::parse_alias, arithmetic::evaluate};
 
-pub const SYSTEM_COMMANDS: [&str; 2] = [
+pub const SYSTEM_COMMANDS: [&str; 23] = [
     "exit",
-    "cd"
+    "cd",
+    "export",
+    "unset",
+    "env",
+    "set",
+    "jobs",
+    "fg",
+    "bg",
+    "wait",
+    "kill",
+    "complete",
+    "source",
+    ".",
+    "alias",
+    "unalias",
+    "return",
+    "local",
+    "break",
+    "continue",
+    "let",
+    ":",
+    "echo"
 ];
 
-pub fn exit(_: &mut Input) -> ! {
-    std::process::exit(0);
+/// The options of the builtins that take any, offered by Tab completion.
+pub const SYSTEM_OPTIONS: [(&str, &[&str]); 7] = [
+    ("export", &["-p"]),
+    ("unset", &["-f", "-v"]),
+    ("set", &["-e", "+e", "-o", "+o", "--"]),
+    ("jobs", &["-l"]),
+    ("kill", &["-l", "-s"]),
+    ("alias", &["-p"]),
+    ("unalias", &["-a"])
+];
+
+/// `exit [n]` leaves the shell with status `n`, or with the status of the
+/// last command it ran.
+pub fn exit(input: &mut Input, command: &Command, streams: &mut Streams) -> i32 {
+    let status = match command.args.first() {
+        Some(arg) => match arg.parse::<i64>() {
+            Ok(status) => status.rem_euclid(256) as i32,
+            Err(_) => {
+                let _ = writeln!(streams.stderr(), "sks: exit: {}: numeric argument required", arg);
+                return 2;
+            }
+        },
+        None => input.status()
+    };
+
+    input.finish(status)
+}
+
+pub fn cd(input: &mut Input, command: &Command, streams: &mut Streams) -> i32 {
+    let (target, print) = match command.args.first().map(String::as_str) {
+        Some("-") => (input.var("OLDPWD"), true),
+        Some(path) => (Some(path.to_string()), false),
+        None => (input.var("HOME"), false)
+    };
+
+    let target = match target {
+        Some(target) => target,
+        None => {
+            let _ = writeln!(streams.stderr(), "sks: cd: {} not set", if print { "OLDPWD" } else { "HOME" });
+            return 1;
+        }
+    };
+
+    if let Err(err) = set_current_dir(&target) {
+        let _ = writeln!(streams.stderr(), "sks: cd: {}: {}", target, err);
+        return 1;
+    }
+
+    let path = current_dir().unwrap();
+    if let Some(old) = input.var("PWD") {
+        input.set_var("OLDPWD", old);
+    }
+
+    input.set_var("PWD", path.to_string_lossy());
+    if print {
+        let _ = writeln!(streams.stdout(), "{}", path.display());
+    }
+
+    input.set_path(path);
+    0
+}
+
+pub fn export(input: &mut Input, command: &Command, streams: &mut Streams) -> i32 {
+    if command.args.is_empty() || command.args == ["-p"] {
+        for (name, value) in input.exported() {
+            let _ = writeln!(streams.stdout(), "export {}=\"{}\"", name, escape(value));
+        }
+
+        return 0;
+    }
+
+    let mut status = 0;
+    for arg in &command.args {
+        let (name, value) = match arg.split_once('=') {
+            Some((name, value)) => (name, Some(value.to_string())),
+            None => (arg.as_str(), None)
+        };
+
+        if !is_name(name) {
+            let _ = writeln!(streams.stderr(), "sks: export: `{}`: not a valid identifier", arg);
+            status = 1;
+            continue;
+        }
+
+        input.export(name, value);
+    }
+
+    status
+}
+
+/// `unset name...` removes variables, or functions with `-f`.
+pub fn unset(input: &mut Input, command: &Command, streams: &mut Streams) -> i32 {
+    let (functions, names) = match command.args.first().map(String::as_str) {
+        Some("-f") => (true, &command.args[1..]),
+        Some("-v") => (false, &command.args[1..]),
+        _ => (false, &command.args[..])
+    };
+
+    let mut status = 0;
+    for name in names {
+        if functions {
+            input.remove_function(name);
+            continue;
+        } else if !is_name(name) {
+            let _ = writeln!(streams.stderr(), "sks: unset: `{}`: not a valid identifier", name);
+            status = 1;
+            continue;
+        }
+
+        input.unset(name);
+    }
+
+    status
+}
+
+pub fn env(input: &mut Input, command: &Command, streams: &mut Streams) -> i32 {
+    // `NAME=value env` shows the variables it was given as well.
+    let mut variables = input.exported()
+        .into_iter()
+        .collect::<BTreeMap<_, _>>();
+
+    for (name, value) in &command.assignments {
+        variables.insert(name, value);
+    }
+
+    for (name, value) in variables {
+        let _ = writeln!(streams.stdout(), "{}={}", name, value);
+    }
+
+    0
+}
+
+/// `set -e` (or `-o errexit`) makes a failing command end the shell and
+/// `-o failglob` makes a pattern that matches no file an error instead of
+/// an argument as typed; `+e` and `+o` turn them off again. The words after
+/// the options, or after `--`, become the positional parameters. Alone it
+/// lists every variable.
+pub fn set(input: &mut Input, command: &Command, streams: &mut Streams) -> i32 {
+    if command.args.is_empty() {
+        for (name, value) in input.variables() {
+            let _ = writeln!(streams.stdout(), "{}=\"{}\"", name, escape(value));
+        }
+
+        return 0;
+    }
+
+    let mut args = command.args.iter();
+    let mut positional = None;
+    while let Some(arg) = args.next() {
+        let (option, enable) = match arg.as_str() {
+            "--" => {
+                positional = Some(args.by_ref().cloned().collect());
+                break;
+            },
+            "-o" | "+o" => match args.next().map(String::as_str) {
+                Some(option @ ("errexit" | "failglob")) => (option, arg == "-o"),
+                Some(option) => {
+                    let _ = writeln!(streams.stderr(), "sks: set: {}: invalid option name", option);
+                    return 2;
+                },
+                None => {
+                    let _ = writeln!(streams.stderr(), "sks: set: {}: option requires an argument", arg);
+                    return 2;
+                }
+            },
+            "-e" => ("errexit", true),
+            "+e" => ("errexit", false),
+            option if option.starts_with(['-', '+']) => {
+                let _ = writeln!(streams.stderr(), "sks: set: {}: invalid option", option);
+                return 2;
+            },
+            _ => {
+                positional = Some(std::iter::once(arg).chain(args.by_ref()).cloned().collect());
+                break;
+            }
+        };
+
+        match option {
+            "errexit" => input.set_errexit(enable),
+            _ => input.set_failglob(enable)
+        }
+    }
+
+    if let Some(positional) = positional {
+        input.set_arguments(positional);
+    }
+
+    0
+}
+
+#[cfg(target_family = "unix")]
+pub fn jobs(input: &mut Input, command: &Command, streams: &mut Streams) -> i32 {
+    let long = command.args.iter().any(|arg| arg == "-l");
+    let specs = command.args
+        .iter()
+        .filter(|arg| *arg != "-l")
+        .collect::<Vec<_>>();
+
+    let mut selected = vec![];
+    for spec in &specs {
+        match find_job(input, Some(spec)) {
+            Ok(idx) => selected.push(idx),
+            Err(err) => {
+                let _ = writeln!(streams.stderr(), "sks: jobs: {}", err);
+                return 1;
+            }
+        }
+    }
+
+    let count = input.jobs().len();
+    for (idx, job) in input.jobs().iter_mut().enumerate() {
+        job.poll();
+        if !specs.is_empty() && !selected.contains(&idx) {
+            continue;
+        }
+
+        let _ = writeln!(streams.stdout(), "{}", job.line(Job::marker(idx, count)));
+        if long {
+            let pids = job.pids()
+                .iter()
+                .map(u32::to_string)
+                .collect::<Vec<_>>();
+
+            let _ = writeln!(streams.stdout(), "      pgid {}: {}", job.pgid().unwrap_or(0), pids.join(" "));
+        }
+    }
+
+    // A finished job is reported once, by whoever saw it first.
+    input.jobs().retain(|job| !matches!(job.state(), JobState::Done(_)));
+    0
+}
+
+#[cfg(target_family = "unix")]
+pub fn fg(input: &mut Input, command: &Command, streams: &mut Streams) -> i32 {
+    let idx = match find_job(input, command.args.first().map(String::as_str)) {
+        Ok(idx) => idx,
+        Err(err) => {
+            let _ = writeln!(streams.stderr(), "sks: fg: {}", err);
+            return 1;
+        }
+    };
+
+    let mut job = input.jobs().remove(idx);
+    let _ = writeln!(streams.stdout(), "{}", job.text());
+    if let Some(pgid) = job.pgid() {
+        if let Some(terminal) = input.terminal() {
+            terminal.give(pgid);
+        }
+    }
+
+    if let Err(err) = job.resume() {
+        let _ = writeln!(streams.stderr(), "sks: fg: {}", err);
+    }
+
+    input.foreground(job)
+}
+
+#[cfg(target_family = "unix")]
+pub fn bg(input: &mut Input, command: &Command, streams: &mut Streams) -> i32 {
+    let idx = match find_job(input, command.args.first().map(String::as_str)) {
+        Ok(idx) => idx,
+        Err(err) => {
+            let _ = writeln!(streams.stderr(), "sks: bg: {}", err);
+            return 1;
+        }
+    };
+
+    // The resumed job becomes the current one.
+    let mut job = input.jobs().remove(idx);
+    if let Err(err) = job.resume() {
+        let _ = writeln!(streams.stderr(), "sks: bg: {}", err);
+        input.jobs().insert(idx, job);
+        return 1;
+    }
+
+    let _ = writeln!(streams.stdout(), "[{}]+ {} &", job.id(), job.text());
+    input.jobs().push(job);
+    0
+}
+
+#[cfg(target_family = "unix")]
+pub fn wait(input: &mut Input, command: &Command, streams: &mut Streams) -> i32 {
+    if command.args.is_empty() {
+        let mut jobs = std::mem::take(input.jobs());
+        for job in &mut jobs {
+            if !input.interrupted() {
+                input.wait(job, false);
+            }
+        }
+
+        // Stopped jobs never finish on their own, so they stay around, and
+        // so do the ones an interrupt stopped us from waiting for.
+        jobs.retain(|job| !matches!(job.state(), JobState::Done(_)));
+        input.jobs().extend(jobs);
+        return if input.interrupted() { 130 } else { 0 };
+    }
+
+    let mut status = 0;
+    for arg in &command.args {
+        let idx = if arg.starts_with('%') {
+            find_job(input, Some(arg))
+        } else {
+            match arg.parse::<u32>() {
+                Ok(pid) => input.jobs()
+                    .iter()
+                    .position(|job| job.pids().contains(&pid))
+                    .ok_or_else(|| format!("pid {} is not a child of this shell", pid)),
+                Err(_) => Err(format!("`{}`: not a pid or valid job spec", arg))
+            }
+        };
+
+        status = match idx {
+            Ok(idx) => {
+                let mut job = input.jobs().remove(idx);
+                input.wait(&mut job, false);
+                if input.interrupted() {
+                    input.jobs().insert(idx, job);
+                    return 130;
+                }
+
+                let status = job.status();
+                if job.state() == JobState::Stopped {
+                    input.jobs().insert(idx, job);
+                }
+
+                status
+            },
+            Err(err) => {
+                let _ = writeln!(streams.stderr(), "sks: wait: {}", err);
+                127
+            }
+        };
+    }
+
+    status
+}
+
+#[cfg(target_family = "unix")]
+pub fn kill(input: &mut Input, command: &Command, streams: &mut Streams) -> i32 {
+    use crate::system::{SIGNALS, signal_number, send_signal};
+
+    let mut args = command.args.iter().map(String::as_str).peekable();
+    let signal = match args.peek().copied() {
+        Some("-l") => {
+            let names = SIGNALS
+                .iter()
+                .map(|(name, number)| format!("{}) SIG{}", number, name))
+                .collect::<Vec<_>>();
+
+            let _ = writeln!(streams.stdout(), "{}", names.join("\n"));
+            return 0;
+        },
+        Some("-s") => {
+            args.next();
+            args.next()
+        },
+        Some(arg) if arg.starts_with('-') => {
+            args.next();
+            Some(&arg[1..])
+        },
+        _ => Some("TERM")
+    };
+
+    let signal = match signal.and_then(signal_number) {
+        Some(signal) => signal,
+        None => {
+            let _ = writeln!(streams.stderr(), "sks: kill: {}: invalid signal specification", signal.unwrap_or_default());
+            return 1;
+        }
+    };
+
+    if args.peek().is_none() {
+        let _ = writeln!(streams.stderr(), "sks: kill: usage: kill [-s signal | -signal] pid | %job ...");
+        return 2;
+    }
+
+    let mut status = 0;
+    for target in args {
+        let result = if target.starts_with('%') {
+            find_job(input, Some(target)).and_then(|idx| {
+                let job = &input.jobs()[idx];
+                job.signal(signal).map_err(|err| format!("{}: {}", target, err))?;
+                // A stopped job would only see the signal once it runs again.
+                if job.state() == JobState::Stopped && signal != libc::SIGSTOP && signal != libc::SIGTSTP {
+                    let _ = job.signal(libc::SIGCONT);
+                }
+
+                Ok(())
+            })
+        } else {
+            match target.parse::<i32>() {
+                Ok(pid) => send_signal(pid, signal).map_err(|err| format!("({}) - {}", pid, err)),
+                Err(_) => Err(format!("{}: arguments must be process or job IDs", target))
+            }
+        };
+
+        if let Err(err) = result {
+            let _ = writeln!(streams.stderr(), "sks: kill: {}", err);
+            status = 1;
+        }
+    }
+
+    status
+}
+
+pub fn source(input: &mut Input, command: &Command, streams: &mut Streams) -> i32 {
+    let path = match command.args.first() {
+        Some(path) => path,
+        None => {
+            let _ = writeln!(streams.stderr(), "sks: {}: filename argument required", command.name);
+            return 2;
+        }
+    };
+
+    match input.source(Path::new(path)) {
+        Ok(status) => status,
+        Err(err) => {
+            let _ = writeln!(streams.stderr(), "sks: {}: {}", command.name, err);
+            input.status()
+        }
+    }
 }
 
-pub fn cd(input: &mut Input, command: &Command) -> () {
-    let temp_path = command.args.iter().nth(0);
-    match temp_path {
-        Some(path) => {
-            set_current_dir(path).unwrap();
-            input.set_path(current_dir().unwrap());
+/// `complete [-W words] [-G glob] [-C command] name...` sets up how the
+/// arguments of the named commands are completed, `complete -r name...`
+/// drops that again and `complete` alone (or with `-p`) lists it all.
+pub fn complete(input: &mut Input, command: &Command, streams: &mut Streams) -> i32 {
+    let mut spec = CompletionSpec::default();
+    let mut remove = false;
+    let mut names = vec![];
+    let mut args = command.args.iter();
+    while let Some(arg) = args.next() {
+        let field = match arg.as_str() {
+            "-W" => &mut spec.words,
+            "-G" => &mut spec.glob,
+            "-C" => &mut spec.command,
+            "-r" => {
+                remove = true;
+                continue;
+            },
+            "-p" => continue,
+            option if option.starts_with('-') && names.is_empty() => {
+                let _ = writeln!(streams.stderr(), "sks: complete: {}: invalid option", option);
+                return 2;
+            },
+            name => {
+                names.push(name);
+                continue;
+            }
+        };
+
+        match args.next() {
+            Some(value) => *field = Some(value.clone()),
+            None => {
+                let _ = writeln!(streams.stderr(), "sks: complete: {}: option requires an argument", arg);
+                return 2;
+            }
+        }
+    }
+
+    if names.is_empty() && !remove {
+        for (name, spec) in input.completion_specs() {
+            let mut line = String::from("complete");
+            for (option, value) in [("-W", &spec.words), ("-G", &spec.glob), ("-C", &spec.command)] {
+                if let Some(value) = value {
+                    line.push_str(&format!(" {} '{}'", option, value.replace('\'', "'\\''")));
+                }
+            }
+
+            let _ = writeln!(streams.stdout(), "{} {}", line, name);
+        }
+
+        return 0;
+    }
+
+    let mut status = 0;
+    for name in names {
+        if !remove {
+            input.set_completion_spec(name, spec.clone());
+        } else if !input.remove_completion_spec(name) {
+            let _ = writeln!(streams.stderr(), "sks: complete: {}: no completion specification", name);
+            status = 1;
+        }
+    }
+
+    status
+}
+
+/// `return [n]` leaves the running function with status `n`, or with the
+/// status of the last command it ran.
+pub fn r#return(input: &mut Input, command: &Command, streams: &mut Streams) -> i32 {
+    if input.depth() == 0 {
+        let _ = writeln!(streams.stderr(), "sks: return: can only be used in a function");
+        return 1;
+    }
+
+    let status = match command.args.first() {
+        Some(arg) => match arg.parse::<i64>() {
+            Ok(status) => status.rem_euclid(256) as i32,
+            Err(_) => {
+                let _ = writeln!(streams.stderr(), "sks: return: {}: numeric argument required", arg);
+                2
+            }
         },
-        None => ()
+        None => input.status()
+    };
+
+    input.set_flow(Some(Flow::Return));
+    status
+}
+
+/// `break [n]` leaves the `n`th enclosing loop, the innermost one by
+/// default, and `continue [n]` goes on with its next round.
+pub fn r#break(input: &mut Input, command: &Command, streams: &mut Streams) -> i32 {
+    jump(input, command, streams, Flow::Break)
+}
+
+pub fn r#continue(input: &mut Input, command: &Command, streams: &mut Streams) -> i32 {
+    jump(input, command, streams, Flow::Continue)
+}
+
+fn jump(input: &mut Input, command: &Command, streams: &mut Streams, flow: fn(usize) -> Flow) -> i32 {
+    let count = match command.args.first().map(|arg| (arg, arg.parse::<usize>())) {
+        None => 1,
+        Some((_, Ok(count))) if count > 0 => count,
+        Some((arg, _)) => {
+            let _ = writeln!(streams.stderr(), "sks: {}: {}: loop count out of range", command.name, arg);
+            return 1;
+        }
+    };
+
+    if input.loops() == 0 {
+        let _ = writeln!(streams.stderr(), "sks: {}: only meaningful in a loop", command.name);
+        return 1;
+    }
+
+    input.set_flow(Some(flow(count.min(input.loops()))));
+    0
+}
+
+/// `local name[=value]...` gives the running function variables of its
+/// own, which are gone again once it returns.
+pub fn local(input: &mut Input, command: &Command, streams: &mut Streams) -> i32 {
+    if input.depth() == 0 {
+        let _ = writeln!(streams.stderr(), "sks: local: can only be used in a function");
+        return 1;
+    }
+
+    let mut status = 0;
+    for arg in &command.args {
+        let (name, value) = match arg.split_once('=') {
+            Some((name, value)) => (name, Some(value.to_string())),
+            None => (arg.as_str(), None)
+        };
+
+        if !is_name(name) {
+            let _ = writeln!(streams.stderr(), "sks: local: `{}`: not a valid identifier", arg);
+            status = 1;
+            continue;
+        }
+
+        input.set_local(name, value);
+    }
+
+    status
+}
+
+/// `let expression...` evaluates the expressions in turn and succeeds when
+/// the last one is not zero.
+pub fn r#let(input: &mut Input, command: &Command, streams: &mut Streams) -> i32 {
+    if command.args.is_empty() {
+        let _ = writeln!(streams.stderr(), "sks: let: expression expected");
+        return 1;
     }
-}
\ No newline at end of file
+
+    let mut value = 0;
+    for arg in &command.args {
+        value = match evaluate(input, arg) {
+            Ok(value) => value,
+            Err(err) => {
+                let _ = writeln!(streams.stderr(), "sks: let: {}", err);
+                return 1;
+            }
+        };
+    }
+
+    (value == 0).into()
+}
+
+/// `echo [-neE] word...` writes the words with spaces in between and a
+/// line break at the end, which `-n` leaves out. `-e` turns on backslash
+/// escapes, where `\c` ends the output right there.
+pub fn echo(_: &mut Input, command: &Command, streams: &mut Streams) -> i32 {
+    let mut newline = true;
+    let mut escapes = false;
+    let mut words = command.args.iter().peekable();
+
+    // Only words made of these letters are options, anything else is the
+    // first word to write.
+    while let Some(flags) = words.peek().and_then(|word| word.strip_prefix('-')) {
+        if flags.is_empty() || !flags.chars().all(|flag| matches!(flag, 'n' | 'e' | 'E')) {
+            break;
+        }
+
+        for flag in flags.chars() {
+            match flag {
+                'n' => newline = false,
+                'e' => escapes = true,
+                _ => escapes = false
+            }
+        }
+
+        words.next();
+    }
+
+    let text = words.map(String::as_str).collect::<Vec<_>>().join(" ");
+    let mut output = if escapes {
+        let (output, stop) = unescape(&text);
+        newline &= !stop;
+        output
+    } else {
+        text.into_bytes()
+    };
+
+    if newline {
+        output.push(b'\n');
+    }
+
+    let stdout = streams.stdout();
+    match stdout.write_all(&output).and_then(|_| stdout.flush()) {
+        Ok(_) => 0,
+        Err(err) => {
+            let _ = writeln!(streams.stderr(), "sks: echo: write error: {}", err);
+            1
+        }
+    }
+}
+
+// The escapes of `echo -e`, and whether `\c` cut the text short.
+fn unescape(text: &str) -> (Vec<u8>, bool) {
+    let mut output = vec![];
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buf = [0; 4];
+            output.extend(c.encode_utf8(&mut buf).as_bytes());
+            continue;
+        }
+
+        let byte = match chars.next() {
+            Some('\\') => b'\\',
+            Some('a') => 0x07,
+            Some('b') => 0x08,
+            Some('c') => return (output, true),
+            Some('e' | 'E') => 0x1b,
+            Some('f') => 0x0c,
+            Some('n') => b'\n',
+            Some('r') => b'\r',
+            Some('t') => b'\t',
+            Some('v') => 0x0b,
+            // Up to three octal digits after `\0`, up to two hexadecimal ones
+            // after `\x`.
+            Some(c @ ('0' | 'x')) => {
+                let (radix, count) = if c == '0' { (8, 3) } else { (16, 2) };
+                let mut value = 0u32;
+                let mut digits = 0;
+                while let Some(digit) = chars.peek().and_then(|digit| digit.to_digit(radix)) {
+                    if digits == count {
+                        break;
+                    }
+
+                    value = value * radix + digit;
+                    digits += 1;
+                    chars.next();
+                }
+
+                if c == 'x' && digits == 0 {
+                    output.extend(b"\\x");
+                    continue;
+                }
+
+                value as u8
+            },
+            Some(other) => {
+                output.push(b'\\');
+                let mut buf = [0; 4];
+                output.extend(other.encode_utf8(&mut buf).as_bytes());
+                continue;
+            },
+            None => b'\\'
+        };
+
+        output.push(byte);
+    }
+
+    (output, false)
+}
+
+/// `alias name=value...` defines aliases, `alias name...` shows them and
+/// `alias` alone (or with `-p`) lists them all.
+pub fn alias(input: &mut Input, command: &Command, streams: &mut Streams) -> i32 {
+    let names = command.args
+        .iter()
+        .filter(|arg| *arg != "-p")
+        .collect::<Vec<_>>();
+
+    if names.is_empty() {
+        for (name, value) in input.aliases() {
+            let _ = writeln!(streams.stdout(), "alias {}='{}'", name, value.replace('\'', "'\\''"));
+        }
+
+        return 0;
+    }
+
+    let mut status = 0;
+    for arg in names {
+        let (name, value) = match (arg.split_once('='), input.alias(arg)) {
+            (Some((name, value)), _) => (name, value),
+            (None, Some(value)) => {
+                let _ = writeln!(streams.stdout(), "alias {}='{}'", arg, value.replace('\'', "'\\''"));
+                continue;
+            },
+            (None, None) => {
+                let _ = writeln!(streams.stderr(), "sks: alias: {}: not found", arg);
+                status = 1;
+                continue;
+            }
+        };
+
+        if name.is_empty() || name.contains(['/', '$', '`', '\'', '"', '\\']) || name.contains(char::is_whitespace) {
+            let _ = writeln!(streams.stderr(), "sks: alias: `{}`: invalid alias name", name);
+            status = 1;
+            continue;
+        }
+
+        // A value that can never run is turned down right away.
+        match parse_alias(name, value) {
+            Ok(_) => input.set_alias(name, value.to_string()),
+            Err(err) => {
+                let _ = writeln!(streams.stderr(), "sks: alias: {}", err);
+                status = 1;
+            }
+        }
+    }
+
+    status
+}
+
+/// `unalias name...` drops aliases, `unalias -a` all of them.
+pub fn unalias(input: &mut Input, command: &Command, streams: &mut Streams) -> i32 {
+    if command.args.is_empty() {
+        let _ = writeln!(streams.stderr(), "sks: unalias: usage: unalias [-a] name...");
+        return 2;
+    }
+
+    let mut status = 0;
+    for name in &command.args {
+        if name == "-a" {
+            input.clear_aliases();
+        } else if !input.remove_alias(name) {
+            let _ = writeln!(streams.stderr(), "sks: unalias: {}: not found", name);
+            status = 1;
+        }
+    }
+
+    status
+}
+
+/// Resolves a job spec to an index into the job table. The table is kept
+/// in the order jobs were last touched, so the current job `%+` is the
+/// last one and the previous job `%-` the one before it.
+#[cfg(target_family = "unix")]
+fn find_job(input: &mut Input, spec: Option<&str>) -> Result<usize, String> {
+    let jobs = input.jobs();
+    let spec = spec.unwrap_or("%+");
+    let name = spec.strip_prefix('%').unwrap_or(spec);
+    let found = match name {
+        "" | "%" | "+" => jobs.len().checked_sub(1),
+        "-" => jobs.len().checked_sub(2).or(jobs.len().checked_sub(1)),
+        _ => match name.parse::<usize>() {
+            Ok(id) => jobs.iter().position(|job| job.id() == id),
+            Err(_) => match name.strip_prefix('?') {
+                Some(text) => jobs.iter().position(|job| job.text().contains(text)),
+                None => jobs.iter().position(|job| job.text().starts_with(name))
+            }
+        }
+    };
+
+    match found {
+        Some(idx) => Ok(idx),
+        None if spec == "%+" && jobs.is_empty() => Err("current: no such job".into()),
+        None => Err(format!("{}: no such job", spec))
+    }
+}
+
+fn escape(value: &str) -> String {
+    let mut escaped = String::new();
+    for c in value.chars() {
+        if matches!(c, '"' | '\\' | '$' | '`') {
+            escaped.push('\\');
+        }
+
+        escaped.push(c);
+    }
+
+    escaped
+}
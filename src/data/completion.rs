@@ -0,0 +1,235 @@
+use std::path::PathBuf;
+
+use crate::{command::{Command, SYSTEM_COMMANDS, SYSTEM_OPTIONS, glob}, format::{Format, Position, Context, ast::{self, Span}}};
+
+use super::{Input, CompletionSpec};
+
+impl Input<'_> {
+    /// Candidates for the word around byte `offset` of `line`, already
+    /// escaped the way they have to be inserted, and the span they replace.
+    pub(super) fn completions(&mut self, line: &str, offset: usize) -> (Span, Vec<String>) {
+        let context = Format::from(line).context(offset);
+        let word = unescape(&line[context.span.0..context.span.1]);
+
+        // A spec set up with `complete` goes first, files are still there
+        // for the arguments it has nothing for.
+        let spec = match context.position {
+            Position::Argument | Position::Option => context.command
+                .as_deref()
+                .and_then(|name| self.completion_spec(name))
+                .cloned(),
+            Position::Command => None
+        };
+
+        let mut candidates = spec
+            .map(|spec| self.spec_candidates(&spec, &context, &word, line))
+            .unwrap_or_default();
+
+        if candidates.is_empty() {
+            candidates = self.default_candidates(&context, &word);
+        }
+
+        candidates.sort();
+        candidates.dedup();
+        (context.span, candidates)
+    }
+
+    fn spec_candidates(&mut self, spec: &CompletionSpec, context: &Context, word: &str, line: &str) -> Vec<String> {
+        let mut candidates = vec![];
+        if let Some(words) = &spec.words {
+            candidates.extend(words
+                .split_whitespace()
+                .filter(|candidate| candidate.starts_with(word))
+                .map(escape));
+        }
+
+        if let Some(pattern) = &spec.glob {
+            let (dir, name) = match pattern.rfind('/') {
+                Some(idx) => (&pattern[..=idx], &pattern[idx + 1..]),
+                None => ("", pattern.as_str())
+            };
+
+            if let Ok(entries) = self.path.join(dir).read_dir() {
+                candidates.extend(entries
+                    .filter_map(Result::ok)
+                    .map(|entry| format!("{}{}", dir, entry.file_name().to_string_lossy()))
+                    .filter(|path| path.starts_with(word) && glob::matches(name, &path[dir.len()..]))
+                    .map(|path| escape(&path)));
+            }
+        }
+
+        // Like bash, the command gets the command name, the word and the one
+        // before it as arguments, and the whole line in `COMP_LINE`.
+        if let Some(command) = &spec.command {
+            let parsed = Format::from(command.as_str())
+                .into_program()
+                .ok()
+                .and_then(|program| program.list.items.into_iter().next())
+                .map(|and_or| and_or.first.commands)
+                .and_then(|commands| commands.into_iter().next())
+                .and_then(|command| match command {
+                    ast::Command::Simple(command) => Some(command),
+                    _ => None
+                });
+
+            if let Some(Ok(command)) = parsed.map(|parsed| Command::from(&parsed, self)) {
+                let name = context.command.clone().unwrap_or_default();
+                let previous = line[..context.span.0].split_whitespace().last().unwrap_or_default();
+                let variables = [("COMP_LINE", line.to_string()), ("COMP_POINT", context.span.1.to_string())];
+                if let Some(output) = command.output(&[&name, word, previous], &variables) {
+                    candidates.extend(output
+                        .lines()
+                        .map(str::trim)
+                        .filter(|candidate| !candidate.is_empty() && candidate.starts_with(word))
+                        .map(escape));
+                }
+            }
+        }
+
+        candidates
+    }
+
+    fn default_candidates(&self, context: &Context, word: &str) -> Vec<String> {
+        let word = word.to_string();
+        match context.position {
+            Position::Command if !word.contains(std::path::MAIN_SEPARATOR) && !word.contains('/') => self.bin_files
+                .iter()
+                .filter_map(|path| path.file_name())
+                .map(|name| name.to_string_lossy().into_owned())
+                .chain(SYSTEM_COMMANDS.iter().map(|name| name.to_string()))
+                .chain(self.aliases().into_iter().map(|(name, _)| name.to_string()))
+                .chain(self.functions.keys().cloned())
+                .filter(|name| name.starts_with(&word))
+                .map(|name| escape(&name))
+                .collect(),
+            Position::Command => self.paths(&word, true),
+            Position::Argument => self.paths(&word, false),
+            Position::Option => SYSTEM_OPTIONS
+                .iter()
+                .filter(|(name, _)| context.command.as_deref() == Some(name))
+                .flat_map(|(_, options)| options.iter())
+                .filter(|option| option.starts_with(&word))
+                .map(|option| option.to_string())
+                .collect()
+        }
+    }
+
+    // Relative paths are taken from the working directory, and a leading
+    // `~` from the home directory while it stays as typed in the line.
+    fn paths(&self, word: &str, executable: bool) -> Vec<String> {
+        let (dir, name) = match word.rfind(['/', std::path::MAIN_SEPARATOR]) {
+            Some(idx) => (&word[..=idx], &word[idx + 1..]),
+            None => ("", word)
+        };
+
+        let base = match (dir.strip_prefix("~/"), self.var("HOME")) {
+            (Some(rest), Some(home)) => PathBuf::from(home).join(rest),
+            _ if dir.is_empty() => self.path.clone(),
+            _ => self.path.join(dir)
+        };
+
+        let entries = match base.read_dir() {
+            Ok(entries) => entries,
+            Err(_) => return vec![]
+        };
+
+        entries
+            .filter_map(Result::ok)
+            .filter_map(|entry| {
+                let file_name = entry.file_name().to_string_lossy().into_owned();
+                if !file_name.starts_with(name) || file_name.starts_with('.') && !name.starts_with('.') {
+                    return None;
+                }
+
+                let path = entry.path();
+                if path.is_dir() {
+                    Some(format!("{}{}/", escape(dir), escape(&file_name)))
+                } else if !executable || is_executable(&path) {
+                    Some(format!("{}{}", escape(dir), escape(&file_name)))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(target_family = "unix")]
+fn is_executable(path: &std::path::Path) -> bool {
+    crate::system::is_executable(path)
+}
+
+#[cfg(target_family = "windows")]
+fn is_executable(_: &std::path::Path) -> bool {
+    true
+}
+
+/// The longest prefix every candidate starts with.
+pub(super) fn common_prefix(candidates: &[String]) -> String {
+    let mut prefix = match candidates.first() {
+        Some(first) => first.clone(),
+        None => return String::new()
+    };
+
+    for candidate in &candidates[1..] {
+        let length = prefix
+            .char_indices()
+            .zip(candidate.chars())
+            .take_while(|((_, a), b)| a == b)
+            .last()
+            .map_or(0, |((idx, c), _)| idx + c.len_utf8());
+
+        prefix.truncate(length);
+    }
+
+    prefix
+}
+
+/// Lays the candidates out in as many columns as fit into `width`, filled
+/// top to bottom like `ls` does. Lines end in `\r\n` for the raw terminal.
+pub(super) fn columns(candidates: &[String], width: usize) -> String {
+    let column = candidates.iter().map(|candidate| candidate.chars().count()).max().unwrap_or(0) + 2;
+    let count = (width / column).max(1);
+    let rows = candidates.len().div_ceil(count);
+
+    let mut result = String::new();
+    for row in 0..rows {
+        let line = (0..count)
+            .filter_map(|col| candidates.get(col * rows + row))
+            .map(|candidate| format!("{:<1$}", candidate, column))
+            .collect::<String>();
+
+        result.push_str(line.trim_end());
+        result.push_str("\r\n");
+    }
+
+    result
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        if c.is_whitespace() || "'\"\\$`&|;<>()*?[]#".contains(c) {
+            escaped.push('\\');
+        }
+
+        escaped.push(c);
+    }
+
+    escaped
+}
+
+// Quotes and backslashes typed so far do not take part in the matching.
+fn unescape(word: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = word.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unescaped.extend(chars.next()),
+            '\'' | '"' => (),
+            c => unescaped.push(c)
+        }
+    }
+
+    unescaped
+}
@@ -0,0 +1,110 @@
+use std::{path::PathBuf, fs::{File, OpenOptions, create_dir_all}, io::{self, Read, Write, Seek, SeekFrom}};
+
+/// `SKS_HISTFILE` when it is set, an empty value turning the history file
+/// off, and otherwise `sks/history` in the user's data directory.
+pub(super) fn history_file() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os("SKS_HISTFILE") {
+        return if path.is_empty() { None } else { Some(path.into()) };
+    }
+
+    #[cfg(target_family = "unix")]
+    let data = std::env::var_os("XDG_DATA_HOME")
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")));
+
+    #[cfg(target_family = "windows")]
+    let data = std::env::var_os("APPDATA").map(PathBuf::from);
+
+    data.map(|data| data.join("sks").join("history"))
+}
+
+pub(super) fn load(path: &PathBuf) -> Vec<String> {
+    let mut content = String::new();
+    match File::open(path) {
+        Ok(mut file) => if file.read_to_string(&mut content).is_err() {
+            return vec![];
+        },
+        Err(_) => return vec![]
+    }
+
+    content.lines().map(decode).collect()
+}
+
+// Every session appends under an exclusive lock with a single write, so
+// the entries of shells running side by side never interleave.
+pub(super) fn append(path: &PathBuf, entry: &str) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        create_dir_all(parent)?;
+    }
+
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    file.lock()?;
+    (&file).write_all(format!("{}\n", encode(entry)).as_bytes())
+}
+
+/// Cuts the file down to its last `limit` entries, dropping the earlier
+/// copies of repeated entries first when `erase_dups` is set. The file is
+/// rewritten in place under the lock, so no append made meanwhile is lost.
+pub(super) fn trim(path: &PathBuf, limit: usize, erase_dups: bool) -> io::Result<()> {
+    let mut file = match OpenOptions::new().read(true).write(true).open(path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err)
+    };
+
+    file.lock()?;
+    let mut content = String::new();
+    file.read_to_string(&mut content)?;
+
+    let mut lines = content.lines().collect::<Vec<_>>();
+    let count = lines.len();
+    if erase_dups {
+        let mut seen = std::collections::HashSet::new();
+        lines.reverse();
+        lines.retain(|line| seen.insert(*line));
+        lines.reverse();
+    }
+
+    if lines.len() > limit {
+        lines.drain(..lines.len() - limit);
+    }
+
+    if lines.len() == count {
+        return Ok(());
+    }
+
+    let mut trimmed = lines.join("\n");
+    if !trimmed.is_empty() {
+        trimmed.push('\n');
+    }
+
+    file.seek(SeekFrom::Start(0))?;
+    file.set_len(0)?;
+    file.write_all(trimmed.as_bytes())
+}
+
+// One entry per line, with the line breaks of multi-line entries escaped.
+fn encode(entry: &str) -> String {
+    entry.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+fn decode(line: &str) -> String {
+    let mut entry = String::new();
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('n')) => {
+                chars.next();
+                entry.push('\n');
+            },
+            ('\\', Some('\\')) => {
+                chars.next();
+                entry.push('\\');
+            },
+            (c, _) => entry.push(c)
+        }
+    }
+
+    entry
+}
@@ -1,11 +1,11 @@
-use std::{io::{stdout, Write}, thread::spawn, time::Duration, path::PathBuf};
+use std::{io::{self, stdin, stdout, Read, Write, IsTerminal}, path::{Path, PathBuf}, sync::atomic::Ordering, collections::HashMap, fmt::Display};
 
 use colored::Colorize;
-use crossbeam_channel::bounded;
-use crossterm::event::{KeyEvent, KeyModifiers, KeyCode};
+use crossbeam_channel::{bounded, unbounded, select};
+use crossterm::event::{Event, KeyEvent, KeyModifiers, KeyCode};
 
-use crate::{command::Command, format::Format, system::load_executable};
-use super::{Input, KeyBoardSignalGenerator, KeyBoardSignal, signal::signal_genertor, Signal, messages::WELCOME, Permission};
+use crate::{command::{Job, JobState, Stream, Streams, Flow, interpreter::run}, format::{Format, ast::{Program, Function}}, system::{load_executable, duplicate}};
+use super::{Input, KeyBoardSignalGenerator, KeyBoardSignal, signal::{signal_genertor, idle_generator}, Signal, Subshell, messages::WELCOME, Permission, Variable, Frame, CompletionSpec, HistorySearch, history, completion::{common_prefix, columns}};
 
 impl<'a> Iterator for Input<'a> {
     type Item = ();
@@ -13,12 +13,7 @@ impl<'a> Iterator for Input<'a> {
     fn next(&mut self) -> Option<Self::Item> {
         match self.signal {
             Signal::Waiting => {
-                let command = self.waiting();
-                if let Some(cmd) = command.clone() {
-                    let process = cmd.execute(self).expect("command expect");
-
-                    self.processing = Some(process);
-                }
+                self.pending = self.waiting();
 
                 Some(())
             },
@@ -45,17 +40,28 @@ impl Iterator for &KeyBoardSignalGenerator {
     type Item = KeyBoardSignal;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let KeyEvent {
-            code,
-            modifiers,
-            ..
-        } = self.recv.recv().unwrap();
+        Some(KeyBoardSignal::from(self.recv.recv().unwrap()))
+    }
+}
+
+impl From<Event> for KeyBoardSignal {
+    fn from(event: Event) -> Self {
+        let (code, modifiers) = match event {
+            Event::Key(KeyEvent {
+                code,
+                modifiers,
+                ..
+            }) => (code, modifiers),
+            Event::Resize(..) => return KeyBoardSignal::Resize,
+            _ => return KeyBoardSignal::None
+        };
 
-        Some(match (code, modifiers) {
+        match (code, modifiers) {
             (KeyCode::Backspace, _) => KeyBoardSignal::BackSpace,
             (KeyCode::Delete, _) => KeyBoardSignal::Delete,
             (KeyCode::Enter, _) => KeyBoardSignal::Enter,
             (KeyCode::Tab, _) => KeyBoardSignal::Tab,
+            (KeyCode::Esc, _) => KeyBoardSignal::Esc,
             (KeyCode::Char(c), KeyModifiers::CONTROL) => KeyBoardSignal::Ctrl(c),
             (KeyCode::Char(c), KeyModifiers::NONE) => KeyBoardSignal::Insert(c),
             (KeyCode::Char(c), KeyModifiers::SHIFT) => KeyBoardSignal::Insert(c.to_ascii_uppercase()),
@@ -67,12 +73,74 @@ impl Iterator for &KeyBoardSignalGenerator {
             (KeyCode::End, _) => KeyBoardSignal::CursorMove(3),
             (KeyCode::F(x), _) => KeyBoardSignal::Fx(x.into()),
             _ => KeyBoardSignal::None
-        })
+        }
+    }
+}
+
+impl HistorySearch {
+    fn new(original: Vec<char>) -> Self {
+        Self {
+            query: String::new(),
+            position: None,
+            failed: false,
+            original
+        }
+    }
+
+    /// Moves to the newest entry before `before` that contains the query.
+    /// When there is none the last match stays and the search is failed.
+    fn find(&mut self, history: &[String], before: usize) {
+        match history[..before.min(history.len())].iter().rposition(|entry| entry.contains(&self.query)) {
+            Some(position) => {
+                self.position = Some(position);
+                self.failed = false;
+            },
+            None => self.failed = true
+        }
+    }
+
+    fn accepted(&self, history: &[String]) -> Vec<char> {
+        match self.position {
+            Some(position) => history[position].chars().collect(),
+            None => self.original.clone()
+        }
+    }
+}
+
+impl KeyBoardSignalGenerator {
+    pub(super) fn enable(&self) {
+        #[cfg(target_family = "unix")]
+        crossterm::terminal::enable_raw_mode().unwrap();
+
+        self.active.store(true, Ordering::Release);
+        self.reader.unpark();
+    }
+
+    pub(super) fn disable(&self) {
+        self.active.store(false, Ordering::Release);
+
+        #[cfg(target_family = "unix")]
+        crossterm::terminal::disable_raw_mode().unwrap();
     }
 }
 
 impl Input<'_> {
+    /// The interactive shell, or a plain line reader when stdin is not a
+    /// terminal, so that other programs can pipe commands in.
     pub fn new() -> Self {
+        let mut input = Self::create(stdin().is_terminal(), vec!["sks".into()]);
+        input.load_history();
+        input
+    }
+
+    /// An input for running a script, with `arguments[0]` as `$0` and the
+    /// rest as the positional parameters. It has no job control and does
+    /// not keep any history.
+    pub fn script(arguments: Vec<String>) -> Self {
+        Self::create(false, arguments)
+    }
+
+    fn create(interactive: bool, arguments: Vec<String>) -> Self {
         let (s, r) = bounded(100);
         Self {
             user_name: "guest".into(),
@@ -81,9 +149,37 @@ impl Input<'_> {
             signal: Signal::Waiting,
             cursor: 0,
             history: vec![],
-            generator: signal_genertor(),
+            history_file: if interactive { history::history_file() } else { None },
+            generator: if interactive { signal_genertor() } else { idle_generator() },
             bin_files: load_executable(),
-            processing: None,
+            bin_paths: std::env::var_os("PATH"),
+            pending: None,
+            continued: None,
+            status: 0,
+            interactive,
+            errexit: false,
+            failglob: false,
+            arguments,
+            variables: std::env::vars_os()
+                .map(|(name, value)| (name.to_string_lossy().into_owned(), Variable {
+                    value: value.to_string_lossy().into_owned(),
+                    exported: true
+                }))
+                .collect(),
+            completion_specs: HashMap::new(),
+            aliases: HashMap::new(),
+            functions: HashMap::new(),
+            frames: vec![],
+            streams: vec![],
+            flow: None,
+            loops: 0,
+            condition: false,
+            subshells: 0,
+            substituted: None,
+            jobs: vec![],
+            last_background: None,
+            #[cfg(target_family = "unix")]
+            terminal: if interactive { crate::system::Terminal::acquire() } else { None },
             sender: s,
             receiver: r
         }
@@ -93,38 +189,552 @@ impl Input<'_> {
         
     // }
 
-    pub fn set_path(&mut self, path: PathBuf) -> () {
+    /// Whether the user types the commands. A command substitution never
+    /// counts as interactive, even in an interactive shell.
+    pub fn interactive(&self) -> bool {
+        self.interactive && self.subshells == 0
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn set_path(&mut self, path: PathBuf) {
         self.path = path;
     }
 
+    pub fn status(&self) -> i32 {
+        self.status
+    }
+
+    pub fn set_status(&mut self, status: i32) {
+        self.status = status;
+    }
+
+    pub fn errexit(&self) -> bool {
+        self.errexit
+    }
+
+    pub fn set_errexit(&mut self, errexit: bool) {
+        self.errexit = errexit;
+    }
+
+    pub fn failglob(&self) -> bool {
+        self.failglob
+    }
+
+    pub fn set_failglob(&mut self, failglob: bool) {
+        self.failglob = failglob;
+    }
+
+    /// `$0` followed by the positional parameters.
+    pub fn arguments(&self) -> &[String] {
+        &self.arguments
+    }
+
+    pub fn set_arguments(&mut self, arguments: Vec<String>) {
+        self.arguments.truncate(1);
+        self.arguments.extend(arguments);
+    }
+
+    pub fn var(&self, name: &str) -> Option<String> {
+        self.variables.get(name).map(|variable| variable.value.clone())
+    }
+
+    // Exported variables are mirrored into the process environment, which
+    // is what spawned commands inherit and what `PATH` lookups read.
+    pub fn set_var(&mut self, name: &str, value: impl Into<String>) {
+        let value = value.into();
+        let variable = self.variables.entry(name.into()).or_insert(Variable {
+            value: String::new(),
+            exported: false
+        });
+
+        if variable.exported {
+            std::env::set_var(name, &value);
+        }
+
+        variable.value = value;
+    }
+
+    pub fn export(&mut self, name: &str, value: Option<String>) {
+        let variable = self.variables.entry(name.into()).or_insert(Variable {
+            value: String::new(),
+            exported: false
+        });
+
+        if let Some(value) = value {
+            variable.value = value;
+        }
+
+        variable.exported = true;
+        std::env::set_var(name, &variable.value);
+    }
+
+    pub fn unset(&mut self, name: &str) {
+        if let Some(variable) = self.variables.remove(name) {
+            if variable.exported {
+                std::env::remove_var(name);
+            }
+        }
+    }
+
+    pub fn variables(&self) -> Vec<(&str, &str)> {
+        let mut variables = self.variables
+            .iter()
+            .map(|(name, variable)| (name.as_str(), variable.value.as_str()))
+            .collect::<Vec<_>>();
+
+        variables.sort();
+        variables
+    }
+
+    pub fn exported(&self) -> Vec<(&str, &str)> {
+        let mut exported = self.variables
+            .iter()
+            .filter(|(_, variable)| variable.exported)
+            .map(|(name, variable)| (name.as_str(), variable.value.as_str()))
+            .collect::<Vec<_>>();
+
+        exported.sort();
+        exported
+    }
+
+    fn history_limit(&self, name: &str, default: usize) -> usize {
+        self.var(name)
+            .and_then(|limit| limit.parse().ok())
+            .unwrap_or(default)
+    }
+
+    // Without `SKS_HISTCONTROL` only repeats of the previous entry are left
+    // out, as the shell always did.
+    fn history_control(&self, option: &str) -> bool {
+        let control = self.var("SKS_HISTCONTROL").unwrap_or_else(|| "ignoredups".into());
+        control.split(':').any(|value| value == option || value == "ignoreboth" && matches!(option, "ignoredups" | "ignorespace"))
+    }
+
+    fn load_history(&mut self) {
+        let path = match &self.history_file {
+            Some(path) => path.clone(),
+            None => return
+        };
+
+        self.trim_history();
+        self.history = history::load(&path);
+
+        let size = self.history_limit("SKS_HISTSIZE", 1000);
+        if self.history.len() > size {
+            self.history.drain(..self.history.len() - size);
+        }
+    }
+
+    /// Records an entered command line, both in memory and at the end of
+    /// the history file.
+    fn push_history(&mut self, entry: String) {
+        if entry.trim().is_empty()
+            || self.history_control("ignorespace") && entry.starts_with(' ')
+            || self.history_control("ignoredups") && self.history.last() == Some(&entry) {
+            return;
+        }
+
+        if self.history_control("erasedups") {
+            self.history.retain(|previous| *previous != entry);
+        }
+
+        if let Some(path) = &self.history_file {
+            if let Err(err) = history::append(path, &entry) {
+                eprintln!("sks: {}: {}", path.display(), err);
+            }
+        }
+
+        self.history.push(entry);
+        let size = self.history_limit("SKS_HISTSIZE", 1000);
+        if self.history.len() > size {
+            self.history.drain(..self.history.len() - size);
+        }
+    }
+
+    fn trim_history(&self) {
+        if let Some(path) = &self.history_file {
+            let limit = self.history_limit("SKS_HISTFILESIZE", 2000);
+            if let Err(err) = history::trim(path, limit, self.history_control("erasedups")) {
+                eprintln!("sks: {}: {}", path.display(), err);
+            }
+        }
+    }
+
+    /// Leaves the shell. Every entry is already in the history file by now,
+    /// it only gets cut down to its size limit.
+    pub fn exit(&mut self, status: i32) -> ! {
+        self.trim_history();
+        std::process::exit(status)
+    }
+
+    /// Leaves the shell, or only the command substitution that is running,
+    /// which stops at the `Flow::Exit` like a loop stops at a `break`.
+    pub fn finish(&mut self, status: i32) -> i32 {
+        if self.subshells == 0 {
+            self.exit(status);
+        }
+
+        self.flow = Some(Flow::Exit);
+        status
+    }
+
+    /// Starts a command substitution, or a stage of a pipeline that cannot
+    /// get a process of its own, taking note of what `leave_subshell` puts
+    /// back.
+    pub fn enter_subshell(&mut self) -> Subshell {
+        self.subshells += 1;
+        Subshell {
+            path: self.path.clone(),
+            arguments: self.arguments.clone(),
+            variables: self.variables.clone(),
+            aliases: self.aliases.clone(),
+            functions: self.functions.clone(),
+            flow: self.flow.take(),
+            loops: self.loops,
+            errexit: self.errexit,
+            failglob: self.failglob
+        }
+    }
+
+    pub fn leave_subshell(&mut self, subshell: Subshell) {
+        self.subshells -= 1;
+        if self.path != subshell.path {
+            let _ = std::env::set_current_dir(&subshell.path);
+            self.path = subshell.path;
+        }
+
+        for (name, variable) in &self.variables {
+            if variable.exported {
+                std::env::remove_var(name);
+            }
+        }
+
+        for (name, variable) in &subshell.variables {
+            if variable.exported {
+                std::env::set_var(name, &variable.value);
+            }
+        }
+
+        self.arguments = subshell.arguments;
+        self.variables = subshell.variables;
+        self.aliases = subshell.aliases;
+        self.functions = subshell.functions;
+        self.flow = subshell.flow;
+        self.loops = subshell.loops;
+        self.errexit = subshell.errexit;
+        self.failglob = subshell.failglob;
+    }
+
+    /// Takes the status of a finished command substitution, which is also
+    /// what a command of only assignments finishes with.
+    pub fn set_substituted(&mut self, status: i32) {
+        self.status = status;
+        self.substituted = Some(status);
+    }
+
+    /// The status of the last command substitution since the previous call.
+    pub fn take_substituted(&mut self) -> Option<i32> {
+        self.substituted.take()
+    }
+
+    pub fn completion_spec(&self, name: &str) -> Option<&CompletionSpec> {
+        self.completion_specs.get(name)
+    }
+
+    pub fn set_completion_spec(&mut self, name: &str, spec: CompletionSpec) {
+        self.completion_specs.insert(name.into(), spec);
+    }
+
+    pub fn remove_completion_spec(&mut self, name: &str) -> bool {
+        self.completion_specs.remove(name).is_some()
+    }
+
+    pub fn completion_specs(&self) -> Vec<(&str, &CompletionSpec)> {
+        let mut specs = self.completion_specs
+            .iter()
+            .map(|(name, spec)| (name.as_str(), spec))
+            .collect::<Vec<_>>();
+
+        specs.sort_by_key(|(name, _)| *name);
+        specs
+    }
+
+    pub fn alias(&self, name: &str) -> Option<&str> {
+        self.aliases.get(name).map(String::as_str)
+    }
+
+    pub fn set_alias(&mut self, name: &str, value: String) {
+        self.aliases.insert(name.into(), value);
+    }
+
+    pub fn remove_alias(&mut self, name: &str) -> bool {
+        self.aliases.remove(name).is_some()
+    }
+
+    pub fn clear_aliases(&mut self) {
+        self.aliases.clear();
+    }
+
+    pub fn function(&self, name: &str) -> Option<&Function> {
+        self.functions.get(name)
+    }
+
+    pub fn set_function(&mut self, function: Function) {
+        self.functions.insert(function.name.clone(), function);
+    }
+
+    pub fn remove_function(&mut self, name: &str) -> bool {
+        self.functions.remove(name).is_some()
+    }
+
+    /// Makes `arguments` the positional parameters until `leave_function`,
+    /// which also undoes every `set_local` made in between.
+    pub fn enter_function(&mut self, arguments: Vec<String>) {
+        let mut previous = vec![self.arguments[0].clone()];
+        previous.extend(arguments);
+        std::mem::swap(&mut self.arguments, &mut previous);
+
+        self.frames.push(Frame {
+            arguments: previous,
+            locals: HashMap::new(),
+            loops: std::mem::take(&mut self.loops)
+        });
+    }
+
+    pub fn leave_function(&mut self) {
+        let frame = match self.frames.pop() {
+            Some(frame) => frame,
+            None => return
+        };
+
+        self.arguments = frame.arguments;
+        self.loops = frame.loops;
+        for (name, variable) in frame.locals {
+            self.unset(&name);
+            if let Some(variable) = variable {
+                if variable.exported {
+                    std::env::set_var(&name, &variable.value);
+                }
+
+                self.variables.insert(name, variable);
+            }
+        }
+    }
+
+    /// How many function calls are running.
+    pub fn depth(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Gives the innermost function call a variable of its own, which is
+    /// unset unless `value` is given. Outside of a function there is
+    /// nothing to be local to.
+    pub fn set_local(&mut self, name: &str, value: Option<String>) {
+        let frame = match self.frames.last_mut() {
+            Some(frame) => frame,
+            None => return
+        };
+
+        if !frame.locals.contains_key(name) {
+            let previous = self.variables.remove(name);
+            if previous.as_ref().is_some_and(|variable| variable.exported) {
+                std::env::remove_var(name);
+            }
+
+            frame.locals.insert(name.into(), previous);
+        }
+
+        if let Some(value) = value {
+            self.set_var(name, value);
+        }
+    }
+
+    /// How many loops are running in the current function, or outside of
+    /// any function.
+    pub fn loops(&self) -> usize {
+        self.loops
+    }
+
+    pub fn enter_loop(&mut self) {
+        self.loops += 1;
+    }
+
+    pub fn leave_loop(&mut self) {
+        self.loops -= 1;
+    }
+
+    /// Whether a condition of `if`, `while` or `until` is running, where a
+    /// failure does not end the shell under `set -e`.
+    pub fn condition(&self) -> bool {
+        self.condition
+    }
+
+    pub fn set_condition(&mut self, condition: bool) {
+        self.condition = condition;
+    }
+
+    pub fn flow(&self) -> Option<Flow> {
+        self.flow
+    }
+
+    pub fn set_flow(&mut self, flow: Option<Flow>) {
+        self.flow = flow;
+    }
+
+    /// Runs `run` with `streams` standing in for standard input, output and
+    /// error, which is how the commands of a function or group follow the
+    /// redirections and pipes of the whole.
+    pub fn with_streams<T>(&mut self, streams: Streams, run: impl FnOnce(&mut Self) -> T) -> T {
+        self.streams.push(streams);
+        let result = run(self);
+        self.streams.pop();
+
+        result
+    }
+
+    pub fn stream(&self, fd: usize) -> Stream {
+        match self.streams.last() {
+            Some(streams) => streams.stream(fd),
+            None => Stream::Inherit
+        }
+    }
+
+    /// Reports an error of the shell itself on the standard error of what
+    /// is running now.
+    pub fn error(&self, message: impl Display) {
+        let _ = writeln!(self.stream(2).as_stderr(), "sks: {}", message);
+    }
+
+    pub fn aliases(&self) -> Vec<(&str, &str)> {
+        let mut aliases = self.aliases
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect::<Vec<_>>();
+
+        aliases.sort();
+        aliases
+    }
+
+    /// Makes this the shell of a forked process running a single stage of
+    /// a pipeline. The terminal, the jobs and the history stay with the
+    /// shell it was forked from.
+    #[cfg(target_family = "unix")]
+    pub fn forked(&mut self) {
+        self.interactive = false;
+        self.terminal = None;
+        self.history_file = None;
+        self.jobs.clear();
+        self.subshells = 0;
+        self.generator = idle_generator();
+        crate::system::default_signals();
+    }
+
+    #[cfg(target_family = "unix")]
+    pub fn terminal(&self) -> Option<&crate::system::Terminal> {
+        self.terminal.as_ref()
+    }
+
+    /// The job table, oldest job first and the current job last.
+    pub fn jobs(&mut self) -> &mut Vec<Job> {
+        &mut self.jobs
+    }
+
+    pub fn last_background(&self) -> Option<u32> {
+        self.last_background
+    }
+
+    // `PATH` can change while the shell is running, so the executable list is
+    // rebuilt whenever it no longer matches the one it was loaded from.
+    fn reload_executable(&mut self) {
+        let bin_paths = std::env::var_os("PATH");
+        if bin_paths != self.bin_paths {
+            self.bin_files = load_executable();
+            self.bin_paths = bin_paths;
+        }
+    }
+
+    /// Runs a script file through the same parser and interpreter as the
+    /// command line and returns the status of the last command in it. A
+    /// script that cannot be read or parsed leaves the status at 1 or 2.
+    pub fn source(&mut self, path: &Path) -> Result<i32, String> {
+        match std::fs::read_to_string(path) {
+            Ok(script) => self.run_script(&script, &path.display().to_string()),
+            Err(err) => {
+                self.status = 1;
+
+                Err(format!("{}: {}", path.display(), err))
+            }
+        }
+    }
+
+    pub fn run_script(&mut self, script: &str, name: &str) -> Result<i32, String> {
+        match Format::from(script).into_program() {
+            Ok(program) => Ok(run(self, &program)),
+            Err(err) => {
+                self.status = 2;
+
+                Err(format!("{}: line {}: {}", name, err.line(script), err))
+            }
+        }
+    }
+
+    /// Sources `~/.sksrc` when there is one.
+    pub fn load_rc(&mut self) {
+        if !self.interactive {
+            return;
+        }
+
+        let rc = match self.var("HOME") {
+            Some(home) => PathBuf::from(home).join(".sksrc"),
+            None => return
+        };
+
+        if rc.is_file() {
+            if let Err(err) = self.source(&rc) {
+                eprintln!("sks: {}", err);
+            }
+        }
+    }
+
     pub fn welcome_message(&self) {
-        print!("{}", WELCOME);
+        if self.interactive {
+            print!("{}", WELCOME);
+        }
     }
 
     fn write(&self, message: impl Into<String>) {
-        stdout().write(message.into().as_bytes()).unwrap();
+        stdout().write_all(message.into().as_bytes()).unwrap();
         stdout().flush().unwrap();
     }
 
     fn write_line(&self) {
-        stdout().write("\n".as_bytes()).unwrap();
+        stdout().write_all("\n".as_bytes()).unwrap();
         stdout().flush().unwrap();
     }
 
     fn prompt_prefix(&self, space: bool) -> usize {
         let (width, _height) = crossterm::terminal::size().unwrap();
-        let prefix_len = self.path.to_str().unwrap().len() + self.user_name.len() + 9;
-        let message = format!(
-            "{} {}({})> ",
-            "(sks)".bold().blue(),
-            self.user_name.bold().red(),
-            self.path.to_str().unwrap().bold());
+        let (prefix_len, message) = match self.continued {
+            // The lines of an unfinished command before the current one.
+            Some(_) => {
+                let message = self.var("PS2").unwrap_or_else(|| "> ".into());
+                (message.chars().count(), message)
+            },
+            None => (self.path.to_str().unwrap().len() + self.user_name.len() + 9, format!(
+                "{} {}({})> ",
+                "(sks)".bold().blue(),
+                self.user_name.bold().red(),
+                self.path.to_str().unwrap().bold()))
+        };
 
         if space {
-            stdout().write(format!("\r{}", String::from(' ').repeat(width.into())).as_bytes()).unwrap();
+            stdout().write_all(format!("\r{}", String::from(' ').repeat(width.into())).as_bytes()).unwrap();
         }
 
-        stdout().write(format!("\r{}", message).as_bytes()).unwrap();
+        stdout().write_all(format!("\r{}", message).as_bytes()).unwrap();
         stdout().flush().unwrap();
 
         prefix_len
@@ -132,51 +742,148 @@ impl Input<'_> {
 }
 
 impl Input<'_> {
-    fn waiting(&mut self) -> Option<Command> {
+    fn waiting(&mut self) -> Option<Program> {
+        if !self.interactive {
+            return self.reading();
+        }
+
         let mut buf = vec![];
         let mut buf_temp = buf.clone();
         let mut history_position = self.history.len();
 
-        let mut searching = false;
-        let mut search_idx = 0;
-        let mut search_buf = vec![];
+        let mut history_search: Option<HistorySearch> = None;
 
+        self.reload_executable();
+        self.notify_jobs();
+        self.generator.enable();
         self.prompt_prefix(true);
-        for signal in &self.generator {
+
+        // Completion may run commands, which needs the whole input.
+        let generator = self.generator.clone();
+        for signal in &generator {
+            if let Some(search) = history_search.as_mut() {
+                let handled = match signal {
+                    KeyBoardSignal::Ctrl('r') => {
+                        search.find(&self.history, search.position.unwrap_or(self.history.len()));
+                        true
+                    },
+                    // A longer query can still match the current entry.
+                    KeyBoardSignal::Insert(c) => {
+                        search.query.push(c);
+                        search.find(&self.history, search.position.map_or(self.history.len(), |position| position + 1));
+                        true
+                    },
+                    KeyBoardSignal::BackSpace => {
+                        search.query.pop();
+                        search.position = None;
+                        search.find(&self.history, self.history.len());
+                        true
+                    },
+                    KeyBoardSignal::Esc | KeyBoardSignal::Ctrl('g') => {
+                        buf = search.original.clone();
+                        self.cursor = buf.len();
+                        history_search = None;
+                        true
+                    },
+                    KeyBoardSignal::Enter => {
+                        buf = search.accepted(&self.history);
+                        self.cursor = buf.len();
+                        history_position = search.position.unwrap_or(history_position);
+                        history_search = None;
+                        true
+                    },
+                    // Any other key takes the match and then does what it
+                    // always does.
+                    _ => {
+                        buf = search.accepted(&self.history);
+                        self.cursor = buf.len();
+                        history_position = search.position.unwrap_or(history_position);
+                        history_search = None;
+                        false
+                    }
+                };
+
+                if handled {
+                    match &history_search {
+                        Some(search) => self.search_prompt(search),
+                        None => {
+                            self.prompt_prefix(true);
+                            self.write(self.render(&buf, None));
+                        }
+                    }
+
+                    continue;
+                }
+            }
+
             match signal {
-                KeyBoardSignal::BackSpace => if self.cursor != 0 {
+                KeyBoardSignal::BackSpace if self.cursor != 0 => {
                     buf.remove(self.cursor - 1);
                     self.cursor -= 1;
                 },
-                KeyBoardSignal::Delete => if self.cursor != buf.len() {
+                KeyBoardSignal::Delete if self.cursor != buf.len() => {
                     buf.remove(self.cursor);
-                }
+                },
                 KeyBoardSignal::Ctrl(c) => match c {
-                    'c' => self.write("^C\n".red().to_string()),
-                    'd' => std::process::exit(0),
+                    'c' => {
+                        self.write("^C\n".red().to_string());
+                        if self.continued.take().is_some() {
+                            buf.clear();
+                            self.cursor = 0;
+                        }
+                    },
+                    'r' => {
+                        let mut search = HistorySearch::new(buf.clone());
+                        search.find(&self.history, self.history.len());
+                        self.search_prompt(&search);
+                        history_search = Some(search);
+
+                        continue;
+                    },
+                    'd' => {
+                        self.generator.disable();
+                        self.write_line();
+                        self.exit(0)
+                    },
                     _ => ()
                 },
                 KeyBoardSignal::CursorMove(m) => match m {
                     0 => self.cursor = 0,
                     3 => self.cursor = buf.len(),
-                    1 => if self.cursor != 0 {
-                        self.cursor -= 1;
-                    },
-                    2 => if self.cursor != buf.len() {
-                        self.cursor += 1;
-                    },
+                    1 if self.cursor != 0 => self.cursor -= 1,
+                    2 if self.cursor != buf.len() => self.cursor += 1,
                     _ => ()
                 },
                 KeyBoardSignal::Enter => {
-                    self.write_line();
-                    self.cursor = 0;
-                    let temp = buf.iter().collect::<String>();
-                    if temp != "" && if let Some(s) = self.history.last() { temp != s.clone() } else { true } {
-                        self.history.push(buf.iter().collect::<String>());
-                    }
+                    let mut entry = self.continued.take().unwrap_or_default();
+                    entry.extend(buf.iter());
+                    let program = self.format(entry.as_str()).into_program();
+
+                    // An unfinished command goes on in the next line.
+                    if program.as_ref().is_err_and(|err| err.is_incomplete()) {
+                        self.write("\r\n");
+                        entry.push('\n');
+                        self.continued = Some(entry);
+                        buf.clear();
+                        self.cursor = 0;
+                        history_position = self.history.len();
+                    } else {
+                        self.generator.disable();
+                        self.write_line();
+                        self.cursor = 0;
+                        self.push_history(entry);
 
-                    self.signal = Signal::Processing;
-                    return Some(Format::from(buf.iter().collect::<String>()).to_command());
+                        self.signal = Signal::Processing;
+                        return match program {
+                            Ok(program) => Some(program),
+                            Err(err) => {
+                                eprintln!("sks: {}", err);
+                                self.status = 2;
+
+                                None
+                            }
+                        };
+                    }
                 },
                 KeyBoardSignal::Fx(_) => (),
                 KeyBoardSignal::History(prev) => if prev {
@@ -184,16 +891,14 @@ impl Input<'_> {
                         buf_temp = buf.clone();
                     }
 
-                    if history_position != 0 {
-                        history_position -= 1;
-                    }
+                    history_position = history_position.saturating_sub(1);
                     
                     if history_position != self.history.len() {
                         buf = self.history[history_position].chars().collect();
                         self.cursor = buf.len();
                     }
                 } else {
-                    if self.history.len() != 0 && history_position == self.history.len() - 1 {
+                    if !self.history.is_empty() && history_position == self.history.len() - 1 {
                         history_position += 1;
                         buf = buf_temp.clone();
                         self.cursor = buf.len();
@@ -210,84 +915,271 @@ impl Input<'_> {
 
                     buf.insert(self.cursor, c);
                     self.cursor += 1;
-                    searching = false;
                 },
                 KeyBoardSignal::Tab => {
-                    if !searching {
-                        searching = true;
-                        search_buf = self.bin_files.iter().filter(|&pb|
-                            pb.file_name()
-                              .unwrap()
-                              .to_str()
-                              .unwrap()
-                              .starts_with(&buf.iter().collect::<String>()))
-                              .map(|pb| pb.file_name().unwrap().to_str().unwrap().to_string())
-                              .collect();
-
-                        search_buf.sort();
+                    let line = buf.iter().collect::<String>();
+                    let offset = line.char_indices().nth(self.cursor).map_or(line.len(), |(idx, _)| idx);
+                    let (span, candidates) = self.completions(&line, offset);
+                    let start = line[..span.0].chars().count();
+
+                    // A unique match is finished off, several ones are
+                    // completed as far as they agree and listed after that.
+                    let mut completion = common_prefix(&candidates);
+                    if candidates.len() == 1 && !completion.ends_with('/') {
+                        completion.push(' ');
                     }
 
-                    buf = search_buf[search_idx].chars().collect();
-                    self.cursor = buf.len();
-                    if search_idx != search_buf.len() - 1 {
-                        search_idx += 1;
-                    } else {
-                        search_idx = 0;
+                    if completion.chars().count() > self.cursor - start {
+                        buf.splice(start..self.cursor, completion.chars());
+                        self.cursor = start + completion.chars().count();
+                    } else if candidates.len() > 1 {
+                        let (width, _height) = crossterm::terminal::size().unwrap();
+                        self.write(format!("\r\n{}", columns(&candidates, width.into())));
                     }
                 },
                 _ => ()
             }
 
             self.prompt_prefix(true);
-            self.write(Format::from(buf.iter().collect::<String>()).transform(None));
+            self.write(self.render(&buf, None));
             if self.cursor != buf.len() {
                 self.prompt_prefix(false);
-                self.write(Format::from(buf.iter().collect::<String>()).transform(Some(self.cursor)));
+                self.write(self.render(&buf, Some(self.cursor)));
             }
         }
 
         None
     }
 
-    fn processing(&mut self) -> () {
-        if let Some(mut child) = self.processing.take() {
-            // let cin = child.stdin.take().unwrap();
-
-            let (s, r) = bounded(1);
-            spawn(move || {
-                loop {
-                    match r.recv_timeout(Duration::from_nanos(100)) {
-                        Ok(_) => return,
-                        _ => ()
-                    }
+    // Without a terminal every line is a command line, unless it leaves a
+    // command unfinished, and the end of the input ends the shell with the
+    // status of the last command.
+    fn reading(&mut self) -> Option<Program> {
+        let mut entry = String::new();
+        let program = loop {
+            match read_line(&mut entry) {
+                Ok(0) if entry.is_empty() => self.exit(self.status),
+                Ok(read) => match Format::from(entry.as_str()).into_program() {
+                    Err(err) if err.is_incomplete() && read > 0 => continue,
+                    program => break program
+                },
+                Err(err) => {
+                    eprintln!("sks: {}", err);
+                    self.exit(1)
                 }
+            }
+        };
+
+        self.signal = Signal::Processing;
+        match program {
+            Ok(program) => Some(program),
+            Err(err) => {
+                eprintln!("sks: {}", err);
+                self.status = 2;
+
+                None
+            }
+        }
+    }
+
+    // Aliased command names are coloured differently from the others.
+    fn format(&self, line: impl Into<String>) -> Format {
+        Format::from(line).aliases(|name| self.aliases.contains_key(name))
+    }
+
+    // The line being edited is coloured along with the lines before it, so
+    // a `do` or a closing quote is not taken out of its context. A recalled
+    // entry of several lines is edited as one, with its line breaks shown as
+    // spaces.
+    fn render(&self, buf: &[char], max: Option<usize>) -> String {
+        let continued = self.continued.as_deref().unwrap_or("");
+        let line = format!("{}{}", continued, buf.iter().collect::<String>());
+        self.format(line).starting_at(continued.len()).transform(max).replace('\n', " ")
+    }
+
+    fn search_prompt(&self, search: &HistorySearch) {
+        let (width, _height) = crossterm::terminal::size().unwrap();
+        let mut line = format!(
+            "\r{}\r({}reverse-i-search)`{}': ",
+            String::from(' ').repeat(width.into()),
+            if search.failed { "failed " } else { "" },
+            search.query);
+
+        if let Some(position) = search.position {
+            let entry = &self.history[position];
+            let format = self.format(entry.as_str());
+            line.push_str(&match entry.find(&search.query) {
+                Some(start) if !search.query.is_empty() => format.highlight((start, start + search.query.len())).transform(None),
+                _ => format.transform(None)
             });
+        }
 
-            while let Ok(code) = child.try_wait() {
-                match code {
-                    Some(_code) => {
-                        match s.send(()) {
-                            _ => {
-                                self.signal = Signal::Waiting;
-                                return;
-                            }
+        self.write(line);
+    }
+
+    fn processing(&mut self) {
+        if let Some(program) = self.pending.take() {
+            run(self, &program);
+        }
+
+        if self.flow == Some(Flow::Exit) {
+            self.exit(self.status);
+        }
+
+        if let Signal::Processing = self.signal {
+            self.signal = Signal::Waiting;
+        }
+    }
+
+    /// Runs a job in the foreground until it finishes or gets stopped, and
+    /// returns its status. A stopped job goes to the job table.
+    pub fn foreground(&mut self, mut job: Job) -> i32 {
+        #[cfg(target_family = "unix")]
+        if let (Some(terminal), Some(pgid)) = (&self.terminal, job.pgid()) {
+            terminal.give(pgid);
+        }
+
+        self.wait(&mut job, true);
+
+        #[cfg(target_family = "unix")]
+        if let Some(terminal) = &self.terminal {
+            terminal.reclaim();
+        }
+
+        // Under job control Ctrl-C reaches the job without passing through
+        // the shell, only how the job ended tells about it.
+        let status = job.status();
+        if job.interrupted() {
+            self.signal = Signal::Interrupt;
+        }
+
+        if job.state() == JobState::Stopped {
+            self.add_job(&mut job);
+            println!();
+            println!("{}", job.line('+'));
+            self.jobs.push(job);
+        }
+
+        status
+    }
+
+    /// Leaves a job running in the background and reports its number and
+    /// the process id `$!` refers to from now on.
+    pub fn background(&mut self, mut job: Job) {
+        let pids = job.pids();
+        if job.state() != JobState::Running || pids.is_empty() {
+            return;
+        }
+
+        self.add_job(&mut job);
+        self.last_background = pids.last().copied();
+        if self.interactive {
+            println!("[{}] {}", job.id(), pids.last().unwrap());
+        }
+
+        self.jobs.push(job);
+    }
+
+    // Job numbers are reused once the jobs holding them are gone.
+    fn add_job(&self, job: &mut Job) {
+        if job.id() == 0 {
+            job.set_id(self.jobs.iter().map(Job::id).max().unwrap_or(0) + 1);
+        }
+    }
+
+    /// Reports the background jobs that finished or stopped since the last
+    /// prompt, and forgets the finished ones.
+    fn notify_jobs(&mut self) {
+        let count = self.jobs.len();
+        for (idx, job) in self.jobs.iter_mut().enumerate() {
+            let state = job.state();
+            job.poll();
+            if job.state() != state && job.state() != JobState::Running {
+                println!("{}", job.line(Job::marker(idx, count)));
+            }
+        }
+
+        self.jobs.retain(|job| !matches!(job.state(), JobState::Done(_)));
+    }
+
+    /// Blocks until the job finishes or gets stopped. The processes are
+    /// waited on by their own threads, while the keyboard signals stay
+    /// deliverable: Ctrl-C interrupts a foreground job and makes waiting
+    /// for a background one give up, leaving it running.
+    pub fn wait(&mut self, job: &mut Job, foreground: bool) {
+        let (sender, changes) = unbounded();
+        job.watch(&sender);
+        while job.state() == JobState::Running {
+            select! {
+                recv(changes) -> idx => if let Ok(idx) = idx {
+                    job.changed(idx, &sender);
+                },
+                recv(self.generator.recv) -> event => if let Ok(event) = event {
+                    if let KeyBoardSignal::Ctrl('c') = KeyBoardSignal::from(event) {
+                        self.signal = Signal::Interrupt;
+                        if !foreground {
+                            return;
                         }
-                    },
-                    _ => ()
+
+                        job.interrupt();
+                    }
                 }
             }
+        }
+    }
 
-            self.signal = Signal::Waiting;
+    /// Takes in a Ctrl-C that came while no job was being waited on, so a
+    /// loop running only builtins can be interrupted as well.
+    pub fn check_interrupt(&mut self) {
+        while let Ok(event) = self.generator.recv.try_recv() {
+            if let KeyBoardSignal::Ctrl('c') = KeyBoardSignal::from(event) {
+                self.signal = Signal::Interrupt;
+            }
         }
     }
 
-    fn interrupt(&mut self) -> () {
-        
+    pub fn interrupted(&self) -> bool {
+        matches!(self.signal, Signal::Interrupt)
+    }
+
+    // Whatever was left of the command line is dropped, like the rest of a
+    // list after the command that got interrupted.
+    fn interrupt(&mut self) {
+        println!();
+        println!("{}", "sks: interrupted".red());
+        self.pending = None;
+        self.status = 130;
+        self.signal = Signal::Waiting;
     }
 
-    fn listening(&mut self) -> () {
+    fn listening(&mut self) {
+
+    }
+}
 
+// Commands piped in share the standard input with the programs they run,
+// so a line is read a byte at a time, leaving the rest of the input for
+// them, instead of through the buffered `stdin()`.
+fn read_line(entry: &mut String) -> io::Result<usize> {
+    let mut input = duplicate(0)?;
+    let mut line = vec![];
+    let mut byte = [0];
+    loop {
+        match input.read(&mut byte) {
+            Ok(0) => break,
+            Ok(_) => {
+                line.push(byte[0]);
+                if byte[0] == b'\n' {
+                    break;
+                }
+            },
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err)
+        }
     }
+
+    entry.push_str(&String::from_utf8_lossy(&line));
+    Ok(line.len())
 }
 
 // impl KeyBoardSignalGenerator {
@@ -1,12 +1,19 @@
 mod impls;
 mod signal;
 mod messages;
+mod history;
+mod completion;
 
-use std::{path::PathBuf, thread::Thread, any::Any, process::Child};
+use std::{path::PathBuf, ffi::OsString, collections::HashMap, thread::Thread, any::Any, sync::{Arc, atomic::AtomicBool}};
 
 use crossbeam_channel::{Receiver, Sender};
-use crossterm::event::KeyEvent;
+use crossterm::event::Event;
 
+use crate::{format::ast::{Program, Function}, command::{Job, Streams, Flow}};
+#[cfg(target_family = "unix")]
+use crate::system::Terminal;
+
+#[allow(dead_code)]
 pub struct Input<'a> {
     user_name: String,
     path: PathBuf,
@@ -15,20 +22,83 @@ pub struct Input<'a> {
 
     cursor: usize,
     history: Vec<String>,
+    history_file: Option<PathBuf>,
     generator: KeyBoardSignalGenerator,
 
     bin_files: Vec<PathBuf>,
-    processing: Option<Child>,
+    bin_paths: Option<OsString>,
+    pending: Option<Program>,
+    continued: Option<String>,
+    status: i32,
+    interactive: bool,
+    errexit: bool,
+    failglob: bool,
+    arguments: Vec<String>,
+    variables: HashMap<String, Variable>,
+    completion_specs: HashMap<String, CompletionSpec>,
+    aliases: HashMap<String, String>,
+    functions: HashMap<String, Function>,
+    frames: Vec<Frame>,
+    streams: Vec<Streams>,
+    flow: Option<Flow>,
+    loops: usize,
+    condition: bool,
+    subshells: usize,
+    substituted: Option<i32>,
+    jobs: Vec<Job>,
+    last_background: Option<u32>,
+    #[cfg(target_family = "unix")]
+    terminal: Option<Terminal>,
 
     sender: Sender<&'a dyn Any>,
     receiver: Receiver<&'a dyn Any>,
 }
 
+/// How the arguments of a command are completed, as set up by `complete`.
+/// Every part that is there contributes candidates.
+#[derive(Debug, Clone, Default)]
+pub struct CompletionSpec {
+    pub words: Option<String>,
+    pub glob: Option<String>,
+    pub command: Option<String>
+}
+
+/// What a function call changes and gets back once it returns: the
+/// positional parameters, the variables it made local and the loops it
+/// cannot `break` out of.
+pub(super) struct Frame {
+    arguments: Vec<String>,
+    locals: HashMap<String, Option<Variable>>,
+    loops: usize
+}
+
+/// What a command substitution gets back once it is done. It runs in the
+/// shell itself, so everything it could change is put back afterwards.
+pub struct Subshell {
+    path: PathBuf,
+    arguments: Vec<String>,
+    variables: HashMap<String, Variable>,
+    aliases: HashMap<String, String>,
+    functions: HashMap<String, Function>,
+    flow: Option<Flow>,
+    loops: usize,
+    errexit: bool,
+    failglob: bool
+}
+
+#[derive(Clone)]
+pub(super) struct Variable {
+    value: String,
+    exported: bool
+}
+
+#[allow(dead_code)]
 pub(super) enum Permission {
     Root,
     Normal
 }
 
+#[allow(dead_code)]
 pub(super) enum Signal {
     Waiting,
     Processing,
@@ -37,20 +107,33 @@ pub(super) enum Signal {
 }
 
 #[derive(Debug)]
+#[allow(dead_code)]
 pub(super) enum KeyBoardSignal {
     CursorMove(usize),
     BackSpace,
     Delete,
     Enter,
     Tab,
+    Esc,
     Ctrl(char),
     Insert(char),
     History(bool),
     Fx(usize),
+    Resize,
     None
 }
 
+/// An incremental search backwards through the history, started by Ctrl-R.
+pub(super) struct HistorySearch {
+    query: String,
+    position: Option<usize>,
+    failed: bool,
+    original: Vec<char>
+}
+
 #[derive(Clone)]
 pub(super) struct KeyBoardSignalGenerator {
-    recv: Receiver<KeyEvent>
+    recv: Receiver<Event>,
+    active: Arc<AtomicBool>,
+    reader: Thread
 }
\ No newline at end of file
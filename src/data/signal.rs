@@ -1,28 +1,22 @@
-use crossbeam_channel::{bounded, Sender};
-use crossterm::event::{KeyEvent, read};
+use std::{sync::{Arc, atomic::{AtomicBool, Ordering}}, thread::{spawn, park}, time::Duration};
+
+use crossbeam_channel::{bounded, never, Sender};
+use crossterm::event::{Event, KeyEvent, KeyCode, KeyModifiers, KeyEventKind, KeyEventState, poll, read};
 
 use super::KeyBoardSignalGenerator;
 
 #[cfg(target_family = "windows")]
-static mut SENDER: Option<Sender<KeyEvent>> = None;
+static mut SENDER: Option<Sender<Event>> = None;
 
 #[cfg(target_family = "windows")]
 pub(super) fn signal_genertor() -> KeyBoardSignalGenerator {
-    use std::thread::spawn;
-
-    use crossterm::event::{Event, KeyCode, KeyModifiers, KeyEventKind, KeyEventState};
     use winapi::um::consoleapi::SetConsoleCtrlHandler;
 
-    let (sender, receviver) = bounded::<KeyEvent>(100);
+    let (sender, receviver) = bounded::<Event>(100);
     
     unsafe extern "system" fn ctrlc(_: u32) -> i32 {
         if let Some(sender) = &SENDER {
-            sender.send(KeyEvent {
-                code: KeyCode::Char('c'),
-                modifiers: KeyModifiers::CONTROL,
-                kind: KeyEventKind::Press,
-                state: KeyEventState::NONE
-            }).unwrap();
+            sender.send(ctrl_event('c')).unwrap();
         }
 
         1
@@ -33,16 +27,93 @@ pub(super) fn signal_genertor() -> KeyBoardSignalGenerator {
         SetConsoleCtrlHandler(Some(ctrlc), 1);
     }
 
+    let active = Arc::new(AtomicBool::new(false));
+    let reader = spawn_reader(sender, active.clone());
+
+    KeyBoardSignalGenerator {
+        recv: receviver,
+        active,
+        reader
+    }
+}
+
+#[cfg(target_family = "unix")]
+pub(super) fn signal_genertor() -> KeyBoardSignalGenerator {
+    use crossterm::terminal::size;
+    use signal_hook::{consts::{SIGINT, SIGTSTP, SIGWINCH}, iterator::Signals};
+
+    let (sender, receviver) = bounded::<Event>(100);
+
+    let signal_sender = sender.clone();
+    let mut signals = Signals::new([SIGINT, SIGTSTP, SIGWINCH]).unwrap();
     spawn(move || {
-        while let Ok(event) = read() {
-            match event {
-                Event::Key(key) => sender.send(key).unwrap(),
-                _ => ()
+        for signal in signals.forever() {
+            let event = match signal {
+                SIGINT => ctrl_event('c'),
+                SIGTSTP => ctrl_event('z'),
+                SIGWINCH => match size() {
+                    Ok((width, height)) => Event::Resize(width, height),
+                    Err(_) => continue
+                },
+                _ => continue
+            };
+
+            if signal_sender.send(event).is_err() {
+                return;
             }
         }
     });
 
+    let active = Arc::new(AtomicBool::new(false));
+    let reader = spawn_reader(sender, active.clone());
+
     KeyBoardSignalGenerator {
-        recv: receviver
+        recv: receviver,
+        active,
+        reader
     }
 }
+
+// Without a terminal nothing gets caught, so SIGINT and SIGTSTP keep their
+// default actions and a script can be interrupted like any other program.
+pub(super) fn idle_generator() -> KeyBoardSignalGenerator {
+    KeyBoardSignalGenerator {
+        recv: never(),
+        active: Arc::new(AtomicBool::new(false)),
+        reader: std::thread::current()
+    }
+}
+
+fn ctrl_event(c: char) -> Event {
+    Event::Key(KeyEvent {
+        code: KeyCode::Char(c),
+        modifiers: KeyModifiers::CONTROL,
+        kind: KeyEventKind::Press,
+        state: KeyEventState::NONE
+    })
+}
+
+// The reader only touches the terminal while the prompt is active, so a
+// running child keeps its own input instead of racing us for it.
+fn spawn_reader(sender: Sender<Event>, active: Arc<AtomicBool>) -> std::thread::Thread {
+    spawn(move || {
+        loop {
+            if !active.load(Ordering::Acquire) {
+                park();
+                continue;
+            }
+
+            match poll(Duration::from_millis(100)) {
+                Ok(true) if active.load(Ordering::Acquire) => match read() {
+                    Ok(event @ (Event::Key(_) | Event::Resize(..))) => if sender.send(event).is_err() {
+                        return;
+                    },
+                    Ok(_) => (),
+                    Err(_) => return
+                },
+                Ok(_) => (),
+                Err(_) => return
+            }
+        }
+    }).thread().clone()
+}
@@ -0,0 +1,261 @@
+pub type Span = (usize, usize);
+
+#[derive(Debug, Clone, Default)]
+pub struct Program {
+    pub list: List
+}
+
+/// Commands run one after another, as separated by `;`.
+#[derive(Debug, Clone, Default)]
+pub struct List {
+    pub items: Vec<AndOr>
+}
+
+/// Pipelines chained by `&&` and `||`, which run depending on the status
+/// of the previous pipeline. A trailing `&` runs them in the background.
+#[derive(Debug, Clone)]
+pub struct AndOr {
+    pub first: Pipeline,
+    pub rest: Vec<(Connector, Pipeline)>,
+    pub background: bool
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Connector {
+    And,
+    Or
+}
+
+#[derive(Debug, Clone)]
+pub struct Pipeline {
+    pub commands: Vec<Command>
+}
+
+#[derive(Debug, Clone)]
+pub enum Command {
+    Simple(SimpleCommand),
+    Compound(CompoundCommand, Vec<Redirect>),
+    Function(Function)
+}
+
+/// Commands that group other commands, like `{ list; }`, and the ones
+/// that decide by the status of a list whether and how often to run
+/// another one.
+#[derive(Debug, Clone)]
+pub enum CompoundCommand {
+    Group(List),
+    If {
+        branches: Vec<(List, List)>,
+        otherwise: Option<List>
+    },
+    While {
+        condition: List,
+        body: List,
+        until: bool
+    },
+    For {
+        name: String,
+        words: Option<Vec<Word>>,
+        body: List
+    },
+    Case {
+        word: Word,
+        items: Vec<CaseItem>
+    }
+}
+
+/// `pattern | pattern) list ;;` in a `case` command.
+#[derive(Debug, Clone)]
+pub struct CaseItem {
+    pub patterns: Vec<Word>,
+    pub body: List
+}
+
+/// `name() body` or `function name body`, defining a command that runs
+/// `body` with its arguments as the positional parameters.
+#[derive(Debug, Clone)]
+pub struct Function {
+    pub name: String,
+    pub body: CompoundCommand
+}
+
+#[derive(Debug, Clone)]
+pub struct SimpleCommand {
+    pub assignments: Vec<Assignment>,
+    pub words: Vec<Word>,
+    pub redirects: Vec<Redirect>
+}
+
+#[derive(Debug, Clone)]
+pub struct Assignment {
+    pub name: String,
+    pub value: Word
+}
+
+#[derive(Debug, Clone)]
+pub struct Redirect {
+    pub fd: u32,
+    pub kind: RedirectKind,
+    pub target: Word
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RedirectKind {
+    Input,
+    Output,
+    Append,
+    Duplicate,
+    OutputAll,
+    AppendAll
+}
+
+#[derive(Debug, Clone)]
+pub struct Word {
+    pub parts: Vec<WordPart>,
+    pub span: Span
+}
+
+#[derive(Debug, Clone)]
+pub enum WordPart {
+    Literal(String),
+    Escaped(char),
+    SingleQuoted(String),
+    DoubleQuoted(Vec<WordPart>),
+    Parameter(Parameter),
+    /// `$(...)` or `` `...` ``, which is replaced by what the commands write.
+    Command { program: Program, text: String },
+    /// `$((...))`, whose expression is expanded before it is evaluated.
+    Arithmetic(Vec<WordPart>)
+}
+
+#[derive(Debug, Clone)]
+pub struct Parameter {
+    pub name: String,
+    pub modifier: Option<Modifier>
+}
+
+/// The forms of `${name...}`; `colon` means an empty value counts as unset.
+#[derive(Debug, Clone)]
+pub enum Modifier {
+    Length,
+    Default { colon: bool, word: Vec<WordPart> },
+    Assign { colon: bool, word: Vec<WordPart> },
+    Alternative { colon: bool, word: Vec<WordPart> },
+    Error { colon: bool, word: Vec<WordPart> }
+}
+
+impl Word {
+    /// The text of a word made of nothing but unquoted characters, which
+    /// reserved words and alias names have to be.
+    pub fn literal(&self) -> Option<&str> {
+        match self.parts.as_slice() {
+            [WordPart::Literal(literal)] => Some(literal),
+            _ => None
+        }
+    }
+
+    /// The word after quote removal but before any expansion.
+    pub fn text(&self) -> String {
+        let mut text = String::new();
+        for part in &self.parts {
+            part.push_text(&mut text);
+        }
+
+        text
+    }
+
+    /// Splits `NAME=value` into the name and the value word. Only an
+    /// unquoted, valid name before the first `=` makes an assignment.
+    pub fn assignment(&self) -> Option<Assignment> {
+        let (first, rest) = self.parts.split_first()?;
+        let literal = match first {
+            WordPart::Literal(literal) => literal,
+            _ => return None
+        };
+
+        let (name, value) = literal.split_once('=')?;
+        if !is_name(name) {
+            return None;
+        }
+
+        let mut parts = vec![];
+        if !value.is_empty() {
+            parts.push(WordPart::Literal(value.into()));
+        }
+
+        parts.extend(rest.iter().cloned());
+        Some(Assignment {
+            name: name.into(),
+            value: Word {
+                parts,
+                span: (self.span.0 + name.len() + 1, self.span.1)
+            }
+        })
+    }
+}
+
+/// Words that only mean something to the shell where a command name would
+/// be, like the braces around a group.
+pub fn is_reserved(word: &str) -> bool {
+    matches!(word,
+        "{" | "}" | "function" | "if" | "then" | "elif" | "else" | "fi" | "while" | "until"
+        | "do" | "done" | "for" | "in" | "case" | "esac")
+}
+
+pub fn is_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c == '_' || c.is_ascii_alphabetic() => chars.all(|c| c == '_' || c.is_ascii_alphanumeric()),
+        _ => false
+    }
+}
+
+impl WordPart {
+    fn push_text(&self, text: &mut String) {
+        match self {
+            WordPart::Literal(s) | WordPart::SingleQuoted(s) => text.push_str(s),
+            WordPart::Escaped(c) => text.push(*c),
+            WordPart::Parameter(parameter) => {
+                let (operator, colon, word) = match &parameter.modifier {
+                    None => {
+                        text.push_str(&format!("${{{}}}", parameter.name));
+                        return;
+                    },
+                    Some(Modifier::Length) => {
+                        text.push_str(&format!("${{#{}}}", parameter.name));
+                        return;
+                    },
+                    Some(Modifier::Default { colon, word }) => ('-', colon, word),
+                    Some(Modifier::Assign { colon, word }) => ('=', colon, word),
+                    Some(Modifier::Alternative { colon, word }) => ('+', colon, word),
+                    Some(Modifier::Error { colon, word }) => ('?', colon, word)
+                };
+
+                text.push_str("${");
+                text.push_str(&parameter.name);
+                if *colon {
+                    text.push(':');
+                }
+
+                text.push(operator);
+                for part in word {
+                    part.push_text(text);
+                }
+
+                text.push('}');
+            },
+            WordPart::DoubleQuoted(parts) => for part in parts {
+                part.push_text(text);
+            },
+            WordPart::Command { text: command, .. } => text.push_str(&format!("$({})", command)),
+            WordPart::Arithmetic(parts) => {
+                text.push_str("$((");
+                for part in parts {
+                    part.push_text(text);
+                }
+
+                text.push_str("))");
+            }
+        }
+    }
+}
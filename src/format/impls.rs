@@ -1,204 +1,320 @@
-use std::ops::Range;
+use std::fmt::Display;
 
 use colored::Colorize;
 
-use crate::command::Command;
-
-use super::Format;
+use super::{Format, ParseError, Context, Position, ast::{Program, Span, Word, WordPart, is_reserved}, lexer::{Lexer, Token, Operator}, parser::Parser};
 
 impl Format {
     #[inline]
     pub fn from(content: impl Into<String>) -> Self {
-        let content: String = content.into();
-        let content = content.as_str();
-        let length = content.len();
-        let mut idx = 0;
-
-        let args_regex = regex::Regex::new(r#"[ ]?([^" ]+|"(?:[^\\"]|\\"| )*"?)"#).unwrap();
-        let option_regex = regex::Regex::new(r#"(?:\B-|--|/)([^"-= ]+)(?:=+| +)?([^"\-= ]+|"(?:[^\\"]|\\"| )*")?"#).unwrap();
+        let raw: String = content.into();
+        let (tokens, error) = Lexer::new(&raw).tokenize();
+        let program = match error {
+            Some(err) => Err(err),
+            None => Parser::new(&tokens).parse()
+        };
 
-        let mut command = None;
+        let mut commands = vec![];
+        let mut keywords = vec![];
         let mut args = vec![];
         let mut options = vec![];
-        while idx != length {
-            if idx == 0 {
-                if let Some(mtc) = args_regex.find_at(&content, idx) {
-                    command = Some((mtc.start(), mtc.end()));
-                    idx = mtc.end();
+        let mut redirects = vec![];
+        let mut separators = vec![];
+        let mut command_position = true;
+        let mut redirect_target = false;
+        for token in &tokens {
+            match token {
+                Token::Word(word) if redirect_target => {
+                    args.push(word.span);
+                    redirect_target = false;
+                },
+                // The word after a reserved word is in command position too.
+                Token::Word(word) if command_position && word.literal().is_some_and(is_reserved) => keywords.push(word.span),
+                Token::Word(word) => if command_position && word.assignment().is_some() {
+                    let (start, end) = word.span;
+                    let value = word.assignment().map_or(end, |assignment| assignment.value.span.0);
+                    options.push([(start, value - 1), (value, end)]);
+                } else if command_position {
+                    commands.push(word.span);
+                    command_position = false;
+                } else if let Some(option) = option_span(&raw, word) {
+                    options.push(option);
+                } else {
+                    args.push(word.span);
+                },
+                Token::Operator(Operator::Redirect(..), span) => {
+                    redirects.push(*span);
+                    redirect_target = true;
+                },
+                Token::Operator(_, span) => {
+                    separators.push(*span);
+                    command_position = true;
                 }
-                
-                continue;
             }
+        }
 
-            if let Some(mtc) = option_regex.find_at(&content, idx) {
-                let (start, end) = (mtc.start(), mtc.end());
-                if start == idx + content[idx..].len() - content[idx..].trim().len() {
-                    if mtc.as_str().contains("=") {
-                        let fs = mtc.as_str().split("=").collect::<Vec<&str>>();
-                        let f = fs[0];
-                        
-                        idx = end;
-                        options.push([(start, start + f.len()), (start + f.len() + 1, end)]);
-                    } else if mtc.as_str().contains(" ") {
-                        let fs = mtc.as_str().split(" ").collect::<Vec<&str>>();
-                        let f = fs[0];
-                        
-                        idx = end;
-                        options.push([(start, start + f.len()), (start + f.len() + 1, end)]);
-                    } else {
-                        idx = end;
-                        options.push([(start, end), (end, end)]);
-                    }
-
-                    continue;
-                }
-            }
+        Self {
+            raw,
+            program,
+            commands,
+            aliases: vec![],
+            keywords,
+            args,
+            options,
+            redirects,
+            separators,
+            highlight: None,
+            start: 0
+        }
+    }
 
-            if let Some(mtc) = args_regex.find_at(&content, idx) {
-                idx = mtc.end();
-                args.push((mtc.start(), mtc.end()));
-                
-                continue;
-            }
+    /// Finds the word that ends at or contains byte `offset`, or an empty
+    /// one starting there, together with the command it belongs to. The
+    /// span runs from the start of the word up to `offset`.
+    pub fn context(&self, offset: usize) -> Context {
+        let boundary = self.separators
+            .iter()
+            .map(|separator| separator.1)
+            .filter(|&end| end <= offset)
+            .max()
+            .unwrap_or(0);
+
+        let command = self.commands
+            .iter()
+            .find(|command| command.0 >= boundary && command.1 < offset)
+            .map(|command| self.raw[command.0..command.1].to_string());
+
+        let contains = |span: &Span| span.0 <= offset && offset <= span.1;
+        let context = |start: usize, position: Position| Context {
+            span: (start, offset),
+            position,
+            command: command.clone()
+        };
 
-            idx += 1;
+        if let Some(word) = self.commands.iter().find(|span| contains(span)) {
+            return context(word.0, Position::Command);
         }
 
-        Self {
-            raw: content.into(),
-            command,
-            args,
-            options
+        if let Some(word) = self.args.iter().find(|span| contains(span)) {
+            return match self.raw[word.0..].starts_with('-') {
+                true => context(word.0, Position::Option),
+                false => context(word.0, Position::Argument)
+            };
+        }
+
+        // `--key=value` and `NAME=value` both complete their value as an
+        // argument; before the `=` only an option is worth completing.
+        if let Some([key, value]) = self.options.iter().find(|[key, value]| contains(&(key.0, value.1))) {
+            let option = self.raw[key.0..].starts_with('-');
+            return match (offset >= value.0 && value.0 > key.1, option) {
+                (true, _) => context(value.0, Position::Argument),
+                (false, true) => context(key.0, Position::Option),
+                (false, false) => context(key.0, Position::Command)
+            };
+        }
+
+        // A new word: the target of a redirection, an argument once there is
+        // a command, and the command itself otherwise.
+        let after_redirect = self.redirects
+            .iter()
+            .filter(|span| span.1 <= offset)
+            .map(|span| span.1)
+            .max()
+            .is_some_and(|end| end >= boundary && self.raw[end..offset].trim().is_empty());
+
+        match (after_redirect, &command) {
+            (false, None) => context(offset, Position::Command),
+            _ => context(offset, Position::Argument)
         }
     }
+
+    /// Marks a byte range of the input to stand out on top of its colours,
+    /// like the part of a history entry a search matched.
+    pub fn highlight(mut self, span: Span) -> Self {
+        self.highlight = Some(span);
+        self
+    }
+
+    /// Leaves out everything before byte `start`, so the last line of a
+    /// command that spans several is coloured as a part of the whole.
+    pub fn starting_at(mut self, start: usize) -> Self {
+        self.start = start.min(self.raw.len());
+        self
+    }
+
+    /// Marks the command names `is_alias` holds for, as typed and without
+    /// quotes, as the aliases they are.
+    pub fn aliases(mut self, is_alias: impl Fn(&str) -> bool) -> Self {
+        self.aliases = self.commands
+            .iter()
+            .filter(|command| is_alias(&self.raw[command.0..command.1]))
+            .copied()
+            .collect();
+
+        self
+    }
+}
+
+// Only words that start with an unquoted dash (or a slash on Windows) are
+// options; `--key=value` is split so the value can be coloured on its own.
+fn option_span(raw: &str, word: &Word) -> Option<[Span; 2]> {
+    let (start, end) = word.span;
+    let text = &raw[start..end];
+    let is_option = match word.parts.first() {
+        Some(WordPart::Literal(s)) if text.len() > 1 => if cfg!(target_family = "windows") {
+            s.starts_with('-') || s.starts_with('/')
+        } else {
+            s.starts_with('-')
+        },
+        _ => false
+    };
+
+    if !is_option {
+        return None;
+    }
+
+    Some(match text.find('=') {
+        Some(idx) => [(start, start + idx), (start + idx + 1, end)],
+        None => [(start, end), (end, end)]
+    })
 }
 
 impl Format {
     pub fn transform(&self, max: Option<usize>) -> String {
-        let mut raw = self.raw.clone();
-        let mut change_len = 0;
-        if let Some(n) = max {
-            raw.replace_range(n.., "");
+        let raw = &self.raw[self.start..];
+        let end = self.start + match max {
+            Some(n) => raw.char_indices().nth(n).map_or(raw.len(), |(idx, _)| idx),
+            None => raw.len()
         };
 
-        if let Some(mut name) = self.command {
-            if let Some(n) = max {
-                if name.1 > n {
-                    name.1 = n;
-                }
+        let mut colors: Vec<(usize, usize, &str)> = vec![];
+        for name in &self.commands {
+            match self.aliases.contains(name) {
+                true => colors.push((name.0, name.1, "alias")),
+                false => colors.push((name.0, name.1, "command"))
             }
+        }
 
-            let length = raw.len();
-            raw.replace_range(
-                name.0 + change_len..name.1 + change_len,
-                &raw.as_str()[
-                    name.0 + change_len ..
-                    name.1 + change_len
-                ].bright_green().to_string());
-                
-            change_len += raw.len() - length;
-            if let Some(n) = max {
-                if name.1 == n {
-                    return raw;
-                }
-            }
+        for keyword in &self.keywords {
+            colors.push((keyword.0, keyword.1, "keyword"));
         }
 
-        let mut colors: Vec<(usize, usize, &str)> = vec![];
         for arg in &self.args {
             colors.push((arg.0, arg.1, "arg"));
         }
 
+        for redirect in &self.redirects {
+            colors.push((redirect.0, redirect.1, "redirect"));
+        }
+
         for option in &self.options {
             colors.push((option[0].0, option[0].1, "key"));
             colors.push((option[1].0, option[1].1, "value"));
         }
 
-        colors.sort_by(|ta, tb| ta.0.cmp(&tb.0));
-        for color in colors {
-            let mut color = color.clone();
-            if let Some(n) = max {
-                if color.1 > n {
-                    color.1 = n;
+        // Everything the parser choked on is shown as an error instead.
+        if let Err(err) = &self.program {
+            for color in colors.iter_mut() {
+                if color.1 > err.span.0 && color.0 < err.span.1 {
+                    color.2 = "error";
                 }
             }
+        }
 
-            let length = raw.len();
-            match color.2 {
-                "arg" => {
-                    raw.replace_range(
-                        color.0 + change_len..color.1 + change_len,
-                        &raw.as_str()[
-                            color.0 + change_len ..
-                            color.1 + change_len
-                        ].bright_cyan().to_string());
-                },
-                "key" => {
-                    raw.replace_range(
-                        color.0 + change_len..color.1 + change_len,
-                        &raw.as_str()[
-                            color.0 + change_len ..
-                            color.1 + change_len
-                        ].bold().bright_yellow().to_string());
-                },
-                "value" => {
-                    raw.replace_range(
-                        color.0 + change_len..color.1 + change_len,
-                        &raw.as_str()[
-                            color.0 + change_len ..
-                            color.1 + change_len
-                        ].bright_red().to_string());
-                },
-                _ => ()
+        colors.sort_by_key(|color| color.0);
+
+        let mut result = String::new();
+        let mut idx = self.start;
+        for (start, stop, kind) in colors {
+            if start >= end {
+                break;
             }
 
-            change_len += raw.len() - length;
-            if let Some(n) = max {
-                if color.1 == n {
-                    return raw;
-                }
+            if stop <= self.start {
+                continue;
             }
+
+            let (start, stop) = (start.max(self.start), stop.min(end));
+            self.paint(&mut result, (idx, start), "");
+            self.paint(&mut result, (start, stop), kind);
+            idx = stop;
         }
 
-        raw
+        self.paint(&mut result, (idx.min(end), end), "");
+        result
+    }
+
+    // The highlighted range can start and stop anywhere, so a coloured span
+    // is cut at its edges and the part inside is shown reversed.
+    fn paint(&self, result: &mut String, (start, stop): Span, kind: &str) {
+        let mut cuts = vec![start, stop];
+        if let Some((from, to)) = self.highlight {
+            cuts.extend([from, to].into_iter().filter(|&cut| cut > start && cut < stop));
+        }
+
+        cuts.sort();
+        for cut in cuts.windows(2) {
+            let text = &self.raw[cut[0]..cut[1]];
+            if text.is_empty() {
+                continue;
+            }
+
+            let colored = match kind {
+                "command" => text.bright_green(),
+                "alias" => text.bold().bright_blue(),
+                "keyword" => text.bold().magenta(),
+                "arg" => text.bright_cyan(),
+                "key" => text.bold().bright_yellow(),
+                "value" => text.bright_red(),
+                "redirect" => text.bright_magenta(),
+                "error" => text.red().underline(),
+                _ => text.normal()
+            };
+
+            match self.highlight {
+                Some((from, to)) if cut[0] >= from && cut[1] <= to => result.push_str(&colored.reversed().to_string()),
+                _ => result.push_str(&colored.to_string())
+            }
+        }
     }
 }
 
 impl Format {
-    pub fn to_command(&self) -> Command {
-        Command::from(self)
+    pub fn into_program(self) -> Result<Program, ParseError> {
+        self.program
     }
+}
 
-    pub fn get_args(&self) -> Vec<String> {
-        self.args
-            .iter()
-            .map(|&(s, e)| *&self.raw.as_str()[s..e].trim())
-            .map(String::from)
-            .collect()
+impl ParseError {
+    pub fn new(message: impl Into<String>, span: Span) -> Self {
+        Self {
+            message: message.into(),
+            span,
+            incomplete: false
+        }
     }
 
-    pub fn get_options(&self) -> Vec<(String, String)> {
-        self.options
-            .iter()
-            .map(|[(ks, ke), (vs, ve)]| (
-                *&self.raw.as_str()[*ks..*ke].trim(),
-                *&self.raw.as_str()[*vs..*ve].trim()
-            ))
-            .map(|(k, v)| (String::from(k), String::from(v)))
-            .collect()
+    /// An error that more input could still fix, like a block that is not
+    /// closed yet.
+    pub fn incomplete(message: impl Into<String>, span: Span) -> Self {
+        Self {
+            incomplete: true,
+            ..Self::new(message, span)
+        }
     }
 
-    pub fn get_name(&self) -> String {
-        if let Some(command) = self.command {
-            self.raw.as_str()[command.0..command.1].to_string()
-        } else {
-            String::new()
-        }
+    pub fn is_incomplete(&self) -> bool {
+        self.incomplete
+    }
+
+    /// The line of `raw` the error is on, counting from one.
+    pub fn line(&self, raw: &str) -> usize {
+        raw[..self.span.0.min(raw.len())].matches('\n').count() + 1
     }
 }
 
-impl Format {
-    fn len(&self) -> usize {
-        self.raw.len()
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "syntax error: {}", self.message)
     }
-}
\ No newline at end of file
+}
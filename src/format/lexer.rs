@@ -0,0 +1,735 @@
+use std::{iter::Peekable, str::CharIndices, fmt::Display};
+
+use super::{ast::{Span, Program, Word, WordPart, Parameter, Modifier, RedirectKind}, parser::Parser, ParseError};
+
+#[derive(Debug, Clone)]
+pub enum Token {
+    Word(Word),
+    Operator(Operator, Span)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    Pipe,
+    And,
+    Or,
+    Semicolon,
+    DoubleSemicolon,
+    Newline,
+    Background,
+    LeftParen,
+    RightParen,
+    Redirect(RedirectKind, Option<u32>)
+}
+
+impl Token {
+    pub fn span(&self) -> Span {
+        match self {
+            Token::Word(word) => word.span,
+            Token::Operator(_, span) => *span
+        }
+    }
+
+    pub fn text(&self) -> String {
+        match self {
+            Token::Word(word) => word.text(),
+            Token::Operator(operator, _) => operator.to_string()
+        }
+    }
+}
+
+impl Display for Operator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Operator::Pipe => write!(f, "|"),
+            Operator::And => write!(f, "&&"),
+            Operator::Or => write!(f, "||"),
+            Operator::Semicolon => write!(f, ";"),
+            Operator::DoubleSemicolon => write!(f, ";;"),
+            Operator::Newline => write!(f, "newline"),
+            Operator::Background => write!(f, "&"),
+            Operator::LeftParen => write!(f, "("),
+            Operator::RightParen => write!(f, ")"),
+            Operator::Redirect(kind, fd) => {
+                if let Some(fd) = fd {
+                    write!(f, "{}", fd)?;
+                }
+
+                write!(f, "{}", match kind {
+                    RedirectKind::Input => "<",
+                    RedirectKind::Output => ">",
+                    RedirectKind::Append => ">>",
+                    RedirectKind::Duplicate => ">&",
+                    RedirectKind::OutputAll => "&>",
+                    RedirectKind::AppendAll => "&>>"
+                })
+            }
+        }
+    }
+}
+
+fn is_operator(c: char) -> bool {
+    matches!(c, '|' | '&' | ';' | '<' | '>' | '(' | ')')
+}
+
+pub struct Lexer<'a> {
+    raw: &'a str,
+    chars: Peekable<CharIndices<'a>>,
+    error: Option<ParseError>
+}
+
+impl<'a> Lexer<'a> {
+    pub fn new(raw: &'a str) -> Self {
+        Self {
+            raw,
+            chars: raw.char_indices().peekable(),
+            error: None
+        }
+    }
+
+    /// Splits the whole input into tokens. Lexing never stops early: when
+    /// the input is malformed the offending token runs to the end of the
+    /// input and the error is returned next to the tokens, so highlighting
+    /// still has something to work with.
+    pub fn tokenize(mut self) -> (Vec<Token>, Option<ParseError>) {
+        let (tokens, _) = self.tokens(None);
+        (tokens, self.error)
+    }
+
+    // Reads tokens up to the end of the input or, for the `$(` at `open`,
+    // up to the `)` that closes it, which is returned as well.
+    fn tokens(&mut self, open: Option<usize>) -> (Vec<Token>, Option<Span>) {
+        let mut tokens = vec![];
+        let mut depth = 0usize;
+
+        loop {
+            self.skip_blank();
+            let next = self.chars.peek().copied();
+            let io_number = next.and_then(|(start, _)| self.io_number(start));
+            match (next, io_number) {
+                (Some((start, '\n')), _) => {
+                    self.chars.next();
+                    tokens.push(Token::Operator(Operator::Newline, (start, start + 1)));
+                },
+                // A comment only starts where a word would.
+                (Some((_, '#')), _) => while self.chars.next_if(|&(_, c)| c != '\n').is_some() {},
+                (Some((_, c)), _) if is_operator(c) => {
+                    let token = self.operator(None);
+                    match token {
+                        Token::Operator(Operator::LeftParen, _) => depth += 1,
+                        Token::Operator(Operator::RightParen, span) if open.is_some() && depth == 0 => {
+                            return (tokens, Some(span));
+                        },
+                        Token::Operator(Operator::RightParen, _) => depth = depth.saturating_sub(1),
+                        _ => ()
+                    }
+
+                    tokens.push(token);
+                },
+                (Some((start, _)), Some((fd, length))) => {
+                    for _ in 0..length {
+                        self.chars.next();
+                    }
+
+                    tokens.push(self.operator(Some((fd, start))));
+                },
+                (Some(_), None) => {
+                    let word = self.word();
+                    tokens.push(Token::Word(word));
+                },
+                (None, _) => break
+            }
+        }
+
+        if let Some(start) = open {
+            self.unterminated("unterminated `$(`", start);
+        }
+
+        (tokens, None)
+    }
+
+    fn offset(&mut self) -> usize {
+        match self.chars.peek() {
+            Some(&(idx, _)) => idx,
+            None => self.raw.len()
+        }
+    }
+
+    // Only the first error is kept, it is the one the user has to fix.
+    fn fail(&mut self, message: &str, start: usize) {
+        if self.error.is_none() {
+            self.error = Some(ParseError::new(message, (start, self.raw.len())));
+        }
+    }
+
+    // The input ended in the middle of a token, the next line may finish it.
+    fn unterminated(&mut self, message: &str, start: usize) {
+        if self.error.is_none() {
+            self.error = Some(ParseError::incomplete(message, (start, self.raw.len())));
+        }
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        match self.chars.peek() {
+            Some(&(_, next)) if next == c => {
+                self.chars.next();
+                true
+            },
+            _ => false
+        }
+    }
+
+    // Line breaks separate commands, so they are tokens of their own,
+    // unless they are escaped to continue the line.
+    fn skip_blank(&mut self) {
+        while let Some(&(idx, c)) = self.chars.peek() {
+            if &self.raw[idx..] == "\\\n" {
+                self.unterminated("unexpected end of input after `\\`", idx);
+            } else if self.raw[idx..].starts_with("\\\n") {
+                self.chars.next();
+            } else if !c.is_whitespace() || c == '\n' {
+                break;
+            }
+
+            self.chars.next();
+        }
+    }
+
+    // A file descriptor number is only special when it is glued to a
+    // redirection, as in `2>`; anywhere else digits are an ordinary word.
+    fn io_number(&self, start: usize) -> Option<(u32, usize)> {
+        let rest = &self.raw[start..];
+        let length = rest.chars().take_while(char::is_ascii_digit).count();
+        if !rest[length..].starts_with(['<', '>']) {
+            return None;
+        }
+
+        rest[..length].parse().ok().map(|fd| (fd, length))
+    }
+
+    fn operator(&mut self, io_number: Option<(u32, usize)>) -> Token {
+        let start = match io_number {
+            Some((_, start)) => start,
+            None => self.offset()
+        };
+
+        let fd = io_number.map(|(fd, _)| fd);
+        let operator = match self.chars.next() {
+            Some((_, '|')) => if self.eat('|') {
+                Operator::Or
+            } else {
+                Operator::Pipe
+            },
+            Some((_, ';')) => if self.eat(';') {
+                Operator::DoubleSemicolon
+            } else {
+                Operator::Semicolon
+            },
+            Some((_, '(')) => Operator::LeftParen,
+            Some((_, ')')) => Operator::RightParen,
+            Some((_, '<')) => Operator::Redirect(RedirectKind::Input, fd),
+            Some((_, '>')) => if self.eat('>') {
+                Operator::Redirect(RedirectKind::Append, fd)
+            } else if self.eat('&') {
+                Operator::Redirect(RedirectKind::Duplicate, fd)
+            } else {
+                Operator::Redirect(RedirectKind::Output, fd)
+            },
+            Some((_, '&')) => if self.eat('&') {
+                Operator::And
+            } else if self.eat('>') {
+                if self.eat('>') {
+                    Operator::Redirect(RedirectKind::AppendAll, None)
+                } else {
+                    Operator::Redirect(RedirectKind::OutputAll, None)
+                }
+            } else {
+                Operator::Background
+            },
+            other => unreachable!("not an operator: {:?}", other)
+        };
+
+        Token::Operator(operator, (start, self.offset()))
+    }
+
+    fn word(&mut self) -> Word {
+        let start = self.offset();
+        let parts = self.parts(|c| c.is_whitespace() || is_operator(c));
+
+        Word {
+            parts,
+            span: (start, self.offset())
+        }
+    }
+
+    /// Reads unquoted word parts up to (but not including) the first
+    /// character for which `stop` holds.
+    fn parts(&mut self, stop: impl Fn(char) -> bool) -> Vec<WordPart> {
+        let mut parts = vec![];
+        let mut literal = String::new();
+
+        while let Some(&(idx, c)) = self.chars.peek() {
+            if stop(c) {
+                break;
+            }
+
+            self.chars.next();
+            match c {
+                '\\' => {
+                    flush(&mut literal, &mut parts);
+                    match self.chars.next() {
+                        // A line that ends in a backslash goes on in the
+                        // next one, which is not there yet.
+                        Some((_, '\n')) if self.chars.peek().is_none() => {
+                            self.unterminated("unexpected end of input after `\\`", idx);
+                        },
+                        Some((_, '\n')) => (),
+                        Some((_, escaped)) => parts.push(WordPart::Escaped(escaped)),
+                        None => {
+                            self.unterminated("unexpected end of input after `\\`", idx);
+                            parts.push(WordPart::Literal("\\".into()));
+                        }
+                    }
+                },
+                '\'' => {
+                    flush(&mut literal, &mut parts);
+                    parts.push(self.single_quoted(idx));
+                },
+                '"' => {
+                    flush(&mut literal, &mut parts);
+                    parts.push(self.double_quoted(idx));
+                },
+                '`' => {
+                    flush(&mut literal, &mut parts);
+                    parts.push(self.backquoted(idx));
+                },
+                '$' => match self.parameter(idx) {
+                    Some(parameter) => {
+                        flush(&mut literal, &mut parts);
+                        parts.push(parameter);
+                    },
+                    None => literal.push('$')
+                },
+                c => literal.push(c)
+            }
+        }
+
+        flush(&mut literal, &mut parts);
+        parts
+    }
+
+    fn single_quoted(&mut self, start: usize) -> WordPart {
+        let mut quoted = String::new();
+        for (_, c) in self.chars.by_ref() {
+            if c == '\'' {
+                return WordPart::SingleQuoted(quoted);
+            }
+
+            quoted.push(c);
+        }
+
+        self.unterminated("unterminated single quote", start);
+        WordPart::SingleQuoted(quoted)
+    }
+
+    fn double_quoted(&mut self, start: usize) -> WordPart {
+        let mut parts = vec![];
+        let mut literal = String::new();
+
+        while let Some((idx, c)) = self.chars.next() {
+            match c {
+                '"' => {
+                    flush(&mut literal, &mut parts);
+                    return WordPart::DoubleQuoted(parts);
+                },
+                // Inside double quotes a backslash only escapes the characters
+                // that would otherwise be special there.
+                '\\' => match self.chars.peek() {
+                    Some(&(_, escaped @ ('"' | '\\' | '$' | '`'))) => {
+                        self.chars.next();
+                        flush(&mut literal, &mut parts);
+                        parts.push(WordPart::Escaped(escaped));
+                    },
+                    Some(&(_, '\n')) => {
+                        self.chars.next();
+                    },
+                    _ => literal.push('\\')
+                },
+                '`' => {
+                    flush(&mut literal, &mut parts);
+                    parts.push(self.backquoted(idx));
+                },
+                '$' => match self.parameter(idx) {
+                    Some(parameter) => {
+                        flush(&mut literal, &mut parts);
+                        parts.push(parameter);
+                    },
+                    None => literal.push('$')
+                },
+                c => literal.push(c)
+            }
+        }
+
+        flush(&mut literal, &mut parts);
+        self.unterminated("unterminated double quote", start);
+        WordPart::DoubleQuoted(parts)
+    }
+
+    // Called right after a `$`; a `$` that does not start a parameter is
+    // kept as a plain character.
+    fn parameter(&mut self, start: usize) -> Option<WordPart> {
+        let name = match self.chars.peek() {
+            Some(&(_, '{')) => {
+                self.chars.next();
+                return Some(self.braced_parameter(start));
+            },
+            Some(&(_, '(')) if self.raw[start + 1..].starts_with("((") => {
+                self.chars.next();
+                self.chars.next();
+                return Some(self.arithmetic(start));
+            },
+            Some(&(_, '(')) => {
+                self.chars.next();
+                return Some(self.substitution(start));
+            },
+            Some(&(_, c)) if is_special_parameter(c) => {
+                self.chars.next();
+                c.to_string()
+            },
+            Some(&(_, c)) if c == '_' || c.is_ascii_alphabetic() => self.name(),
+            _ => return None
+        };
+
+        Some(WordPart::Parameter(Parameter {
+            name,
+            modifier: None
+        }))
+    }
+
+    // The commands of a `$(...)` are tokens like any others, which is what
+    // lets them hold quotes and further substitutions.
+    fn substitution(&mut self, start: usize) -> WordPart {
+        let (tokens, close) = self.tokens(Some(start));
+        let end = close.map_or(self.raw.len(), |close| close.0);
+
+        WordPart::Command {
+            program: self.program(Parser::new(&tokens).parse()),
+            text: self.raw[start + 2..end].into()
+        }
+    }
+
+    // The expression of a `$((...))` runs up to the `))` that closes it, and
+    // only parameters, substitutions and double quotes are special in it.
+    fn arithmetic(&mut self, start: usize) -> WordPart {
+        let mut parts = vec![];
+        let mut literal = String::new();
+        let mut depth = 0usize;
+
+        while let Some((idx, c)) = self.chars.next() {
+            match c {
+                ')' if depth == 0 => {
+                    if !self.eat(')') {
+                        match self.chars.peek() {
+                            Some(_) => self.fail("expected `))`", start),
+                            None => self.unterminated("unterminated `$((`", start)
+                        }
+                    }
+
+                    flush(&mut literal, &mut parts);
+                    return WordPart::Arithmetic(parts);
+                },
+                '(' => {
+                    depth += 1;
+                    literal.push(c);
+                },
+                ')' => {
+                    depth -= 1;
+                    literal.push(c);
+                },
+                '"' => {
+                    flush(&mut literal, &mut parts);
+                    parts.push(self.double_quoted(idx));
+                },
+                '`' => {
+                    flush(&mut literal, &mut parts);
+                    parts.push(self.backquoted(idx));
+                },
+                '$' => match self.parameter(idx) {
+                    Some(parameter) => {
+                        flush(&mut literal, &mut parts);
+                        parts.push(parameter);
+                    },
+                    None => literal.push('$')
+                },
+                c => literal.push(c)
+            }
+        }
+
+        flush(&mut literal, &mut parts);
+        self.unterminated("unterminated `$((`", start);
+        WordPart::Arithmetic(parts)
+    }
+
+    // Inside backquotes a backslash only escapes `$`, `` ` `` and itself;
+    // what is left after that is a command of its own.
+    fn backquoted(&mut self, start: usize) -> WordPart {
+        let mut text = String::new();
+        while let Some((_, c)) = self.chars.next() {
+            match c {
+                '`' => {
+                    let (tokens, error) = Lexer::new(&text).tokenize();
+                    let program = match error {
+                        Some(err) => Err(err),
+                        None => Parser::new(&tokens).parse()
+                    };
+
+                    // Spans in there are not ones of this input.
+                    let program = program.map_err(|err| ParseError::new(err.message, (start, self.offset())));
+                    return WordPart::Command {
+                        program: self.program(program),
+                        text
+                    };
+                },
+                '\\' => match self.chars.next_if(|&(_, c)| matches!(c, '$' | '`' | '\\')) {
+                    Some((_, escaped)) => text.push(escaped),
+                    None => text.push('\\')
+                },
+                c => text.push(c)
+            }
+        }
+
+        self.unterminated("unterminated backquote", start);
+        WordPart::Command {
+            program: Program::default(),
+            text
+        }
+    }
+
+    fn program(&mut self, program: Result<Program, ParseError>) -> Program {
+        program.unwrap_or_else(|err| {
+            if self.error.is_none() {
+                self.error = Some(err);
+            }
+
+            Program::default()
+        })
+    }
+
+    fn name(&mut self) -> String {
+        let mut name = String::new();
+        while let Some(&(_, c)) = self.chars.peek() {
+            if c != '_' && !c.is_ascii_alphanumeric() {
+                break;
+            }
+
+            name.push(c);
+            self.chars.next();
+        }
+
+        name
+    }
+
+    fn braced_parameter(&mut self, start: usize) -> WordPart {
+        let length = self.eat('#');
+        let name = match self.chars.peek() {
+            Some(&(_, c)) if is_special_parameter(c) => {
+                self.chars.next();
+                c.to_string()
+            },
+            Some(&(_, c)) if c == '_' || c.is_ascii_alphabetic() => self.name(),
+            // `${#}` is the parameter `#` itself rather than a length.
+            Some(&(_, '}')) if length => "#".into(),
+            _ => String::new()
+        };
+
+        let mut parameter = Parameter {
+            name,
+            modifier: None
+        };
+
+        if parameter.name.is_empty() {
+            match self.chars.peek() {
+                Some(_) => self.fail("bad substitution", start),
+                None => self.unterminated("unterminated `${`", start)
+            }
+
+            return WordPart::Parameter(parameter);
+        }
+
+        if length && parameter.name != "#" {
+            parameter.modifier = Some(Modifier::Length);
+        } else if length {
+            parameter.name = "#".into();
+        }
+
+        if parameter.modifier.is_none() {
+            let colon = self.eat(':');
+            let kind = match self.chars.peek() {
+                Some(&(_, kind @ ('-' | '=' | '+' | '?'))) => {
+                    self.chars.next();
+                    Some(kind)
+                },
+                _ => None
+            };
+
+            match kind {
+                Some(kind) => {
+                    let word = self.parts(|c| c == '}');
+                    parameter.modifier = Some(match kind {
+                        '-' => Modifier::Default { colon, word },
+                        '=' => Modifier::Assign { colon, word },
+                        '+' => Modifier::Alternative { colon, word },
+                        _ => Modifier::Error { colon, word }
+                    });
+                },
+                None if colon => {
+                    self.fail("bad substitution", start);
+                    return WordPart::Parameter(parameter);
+                },
+                None => ()
+            }
+        }
+
+        if !self.eat('}') {
+            if self.chars.peek().is_some() {
+                self.fail("bad substitution", start);
+            } else {
+                self.unterminated("unterminated `${`", start);
+            }
+        }
+
+        WordPart::Parameter(parameter)
+    }
+}
+
+fn is_special_parameter(c: char) -> bool {
+    matches!(c, '?' | '$' | '#' | '@' | '*' | '!' | '0'..='9')
+}
+
+fn flush(literal: &mut String, parts: &mut Vec<WordPart>) {
+    if !literal.is_empty() {
+        parts.push(WordPart::Literal(std::mem::take(literal)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(raw: &str) -> Vec<Token> {
+        let (tokens, error) = Lexer::new(raw).tokenize();
+        assert!(error.is_none(), "{}: {:?}", raw, error);
+        tokens
+    }
+
+    fn words(raw: &str) -> Vec<Word> {
+        tokens(raw)
+            .into_iter()
+            .filter_map(|token| match token {
+                Token::Word(word) => Some(word),
+                Token::Operator(..) => None
+            })
+            .collect()
+    }
+
+    fn error(raw: &str) -> ParseError {
+        match Lexer::new(raw).tokenize() {
+            (_, Some(err)) => err,
+            (tokens, None) => panic!("{}: no error in {:?}", raw, tokens)
+        }
+    }
+
+    #[test]
+    fn splits_words_and_operators() {
+        let texts = tokens("a|b&&c ||d;e & f;;\n# comment\ng")
+            .iter()
+            .map(Token::text)
+            .collect::<Vec<_>>();
+
+        assert_eq!(texts, ["a", "|", "b", "&&", "c", "||", "d", ";", "e", "&", "f", ";;", "newline", "newline", "g"]);
+    }
+
+    #[test]
+    fn redirections_take_their_file_descriptor() {
+        let texts = tokens("cmd 2>&1 >out 2>>log <in &>all 3x")
+            .iter()
+            .map(Token::text)
+            .collect::<Vec<_>>();
+
+        assert_eq!(texts, ["cmd", "2>&", "1", ">", "out", "2>>", "log", "<", "in", "&>", "all", "3x"]);
+    }
+
+    #[test]
+    fn quotes_and_escapes_stay_in_one_word() {
+        let words = words(r#"'a b'"c $x"\d e\ f"#);
+        assert_eq!(words.len(), 2);
+        assert!(matches!(words[0].parts.as_slice(), [
+            WordPart::SingleQuoted(single),
+            WordPart::DoubleQuoted(double),
+            WordPart::Escaped('d')
+        ] if single == "a b" && double.len() == 2));
+        assert_eq!(words[0].text(), "a bc ${x}d");
+        assert_eq!(words[1].text(), "e f");
+        assert_eq!(words[1].literal(), None);
+    }
+
+    #[test]
+    fn double_quotes_only_escape_special_characters() {
+        let words = words(r#""\$ \" \\ \a""#);
+        assert_eq!(words[0].text(), r#"$ " \ \a"#);
+    }
+
+    #[test]
+    fn escaped_line_breaks_join_lines() {
+        let words = words("ec\\\nho \\\n x");
+        assert_eq!(words.iter().map(Word::text).collect::<Vec<_>>(), ["echo", "x"]);
+    }
+
+    #[test]
+    fn spans_are_byte_offsets() {
+        let spans = words("é 'ü' x").iter().map(|word| word.span).collect::<Vec<_>>();
+        assert_eq!(spans, [(0, 2), (3, 7), (8, 9)]);
+    }
+
+    #[test]
+    fn parameters() {
+        let words = words("$x ${#y} ${z:-d} $1 $? $");
+        let names = words
+            .iter()
+            .flat_map(|word| &word.parts)
+            .filter_map(|part| match part {
+                WordPart::Parameter(parameter) => Some(parameter.name.as_str()),
+                _ => None
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(names, ["x", "y", "z", "1", "?"]);
+        assert!(matches!(&words[1].parts[0], WordPart::Parameter(Parameter { modifier: Some(Modifier::Length), .. })));
+        assert!(matches!(&words[2].parts[0], WordPart::Parameter(Parameter { modifier: Some(Modifier::Default { colon: true, .. }), .. })));
+        assert_eq!(words[5].literal(), Some("$"));
+    }
+
+    #[test]
+    fn substitutions() {
+        let words = words("$(echo $(date) ')') `pwd` \"$(a)\" $((1 + (2)))");
+        assert!(matches!(&words[0].parts[0], WordPart::Command { text, .. } if text == "echo $(date) ')'"));
+        assert!(matches!(&words[1].parts[0], WordPart::Command { text, .. } if text == "pwd"));
+        assert!(matches!(&words[2].parts[0], WordPart::DoubleQuoted(parts) if matches!(parts[0], WordPart::Command { .. })));
+        assert!(matches!(&words[3].parts[0], WordPart::Arithmetic(parts) if matches!(&parts[0], WordPart::Literal(text) if text == "1 + (2)")));
+        assert_eq!(words[3].text(), "$((1 + (2)))");
+    }
+
+    #[test]
+    fn unfinished_input_is_incomplete() {
+        for raw in ["'a", "\"a", "a \\", "a \\\n", "${x", "${", "$(echo", "$(echo 'a", "`echo", "$((1", "$((1)"] {
+            assert!(error(raw).is_incomplete(), "{}", raw);
+        }
+    }
+
+    #[test]
+    fn malformed_input_is_not_incomplete() {
+        for raw in ["${}", "${x:}", "${x y}", "$((1) + 2))", "`a '`"] {
+            assert!(!error(raw).is_incomplete(), "{}", raw);
+        }
+    }
+}
@@ -1,9 +1,43 @@
 mod impls;
+mod lexer;
+mod parser;
+pub mod ast;
+
+use ast::{Program, Span};
 
 #[derive(Debug)]
 pub struct Format {
     raw: String,
-    command: Option<(usize, usize)>,
-    args: Vec<(usize, usize)>,
-    options: Vec<[(usize, usize); 2]>
-}
\ No newline at end of file
+    program: Result<Program, ParseError>,
+    commands: Vec<Span>,
+    aliases: Vec<Span>,
+    keywords: Vec<Span>,
+    args: Vec<Span>,
+    options: Vec<[Span; 2]>,
+    redirects: Vec<Span>,
+    separators: Vec<Span>,
+    highlight: Option<Span>,
+    start: usize
+}
+
+/// The word the cursor is in, as far as completion is concerned.
+#[derive(Debug, Clone)]
+pub struct Context {
+    pub span: Span,
+    pub position: Position,
+    pub command: Option<String>
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Position {
+    Command,
+    Argument,
+    Option
+}
+
+#[derive(Debug, Clone)]
+pub struct ParseError {
+    message: String,
+    span: Span,
+    incomplete: bool
+}
@@ -0,0 +1,576 @@
+use super::{ast::{Program, List, AndOr, Connector, Pipeline, Command, CompoundCommand, CaseItem, Function, SimpleCommand, Word, Redirect, RedirectKind, is_name, is_reserved}, lexer::{Token, Operator}, ParseError};
+
+pub struct Parser<'a> {
+    tokens: &'a [Token],
+    idx: usize
+}
+
+impl<'a> Parser<'a> {
+    pub fn new(tokens: &'a [Token]) -> Self {
+        Self {
+            tokens,
+            idx: 0
+        }
+    }
+
+    pub fn parse(mut self) -> Result<Program, ParseError> {
+        let list = self.list()?;
+
+        if let Some(token) = self.peek() {
+            return Err(unexpected(token));
+        }
+
+        Ok(Program {
+            list
+        })
+    }
+
+    fn peek(&self) -> Option<&'a Token> {
+        self.tokens.get(self.idx)
+    }
+
+    fn eat(&mut self, operator: Operator) -> Option<&'a Token> {
+        match self.peek() {
+            Some(token @ Token::Operator(op, _)) if *op == operator => {
+                self.idx += 1;
+                Some(token)
+            },
+            _ => None
+        }
+    }
+
+    // Reserved words are only reserved where a command name would be, and
+    // only when they are not quoted.
+    fn reserved(&self, word: &str) -> Option<&'a Token> {
+        match self.peek() {
+            Some(token @ Token::Word(found)) if found.literal() == Some(word) => Some(token),
+            _ => None
+        }
+    }
+
+    fn eat_reserved(&mut self, word: &str) -> Option<&'a Token> {
+        let token = self.reserved(word)?;
+        self.idx += 1;
+        Some(token)
+    }
+
+    fn skip_newlines(&mut self) -> bool {
+        let mut skipped = false;
+        while self.eat(Operator::Newline).is_some() {
+            skipped = true;
+        }
+
+        skipped
+    }
+
+    fn list(&mut self) -> Result<List, ParseError> {
+        let mut items = vec![];
+        self.skip_newlines();
+        while let Some(mut and_or) = self.and_or()? {
+            and_or.background = self.eat(Operator::Background).is_some();
+            let separated = and_or.background || self.eat(Operator::Semicolon).is_some();
+            let separated = self.skip_newlines() || separated;
+            items.push(and_or);
+            if !separated {
+                break;
+            }
+        }
+
+        Ok(List {
+            items
+        })
+    }
+
+    fn and_or(&mut self) -> Result<Option<AndOr>, ParseError> {
+        let first = match self.pipeline()? {
+            Some(pipeline) => pipeline,
+            None => return Ok(None)
+        };
+
+        let mut rest = vec![];
+        loop {
+            let (connector, operator) = if let Some(operator) = self.eat(Operator::And) {
+                (Connector::And, operator)
+            } else if let Some(operator) = self.eat(Operator::Or) {
+                (Connector::Or, operator)
+            } else {
+                break;
+            };
+
+            self.skip_newlines();
+            match self.pipeline()? {
+                Some(pipeline) => rest.push((connector, pipeline)),
+                None => return Err(self.expected_command(operator))
+            }
+        }
+
+        Ok(Some(AndOr {
+            first,
+            rest,
+            background: false
+        }))
+    }
+
+    fn pipeline(&mut self) -> Result<Option<Pipeline>, ParseError> {
+        let mut commands = match self.command()? {
+            Some(command) => vec![command],
+            None => return Ok(None)
+        };
+
+        while let Some(pipe) = self.eat(Operator::Pipe) {
+            self.skip_newlines();
+            match self.command()? {
+                Some(command) => commands.push(command),
+                None => return Err(self.expected_command(pipe))
+            }
+        }
+
+        Ok(Some(Pipeline {
+            commands
+        }))
+    }
+
+    fn command(&mut self) -> Result<Option<Command>, ParseError> {
+        if let Some(keyword) = self.eat_reserved("function") {
+            return self.function(keyword).map(Some);
+        }
+
+        if let (Some(Token::Word(word)), Some(Token::Operator(Operator::LeftParen, _))) = (self.peek(), self.tokens.get(self.idx + 1)) {
+            if word.literal().is_some_and(|name| !is_reserved(name)) {
+                return self.function(&self.tokens[self.idx]).map(Some);
+            }
+        }
+
+        if let Some(compound) = self.compound_command()? {
+            let redirects = self.redirects()?;
+            return Ok(Some(Command::Compound(compound, redirects)));
+        }
+
+        Ok(self.simple_command()?.map(Command::Simple))
+    }
+
+    // Called with the name of the function or the `function` in front of
+    // it as the next token, which is only for error messages.
+    fn function(&mut self, start: &Token) -> Result<Command, ParseError> {
+        let name = match self.peek() {
+            Some(Token::Word(word)) => match word.literal() {
+                Some(name) if !is_reserved(name) && !name.contains('=') => name.to_string(),
+                _ => return Err(ParseError::new(format!("`{}`: not a valid function name", word.text()), word.span))
+            },
+            Some(token) => return Err(unexpected(token)),
+            None => return Err(ParseError::incomplete("expected a function name", start.span()))
+        };
+
+        self.idx += 1;
+        if let Some(paren) = self.eat(Operator::LeftParen) {
+            if self.eat(Operator::RightParen).is_none() {
+                return Err(match self.peek() {
+                    Some(token) => unexpected(token),
+                    None => ParseError::incomplete("expected `)`", paren.span())
+                });
+            }
+        }
+
+        self.skip_newlines();
+        match self.compound_command()? {
+            Some(body) => Ok(Command::Function(Function {
+                name,
+                body
+            })),
+            None => Err(match self.peek() {
+                Some(token) => unexpected(token),
+                None => ParseError::incomplete(format!("expected the body of `{}`", name), start.span())
+            })
+        }
+    }
+
+    fn compound_command(&mut self) -> Result<Option<CompoundCommand>, ParseError> {
+        if let Some(open) = self.eat_reserved("{") {
+            let list = self.list()?;
+            self.close(open, "}")?;
+
+            return Ok(Some(CompoundCommand::Group(list)));
+        }
+
+        if let Some(open) = self.eat_reserved("if") {
+            return self.if_clause(open).map(Some);
+        }
+
+        for word in ["while", "until"] {
+            if let Some(open) = self.eat_reserved(word) {
+                let condition = self.body(open, "do")?;
+                self.close(open, "do")?;
+                let body = self.body(open, "done")?;
+                self.close(open, "done")?;
+
+                return Ok(Some(CompoundCommand::While {
+                    condition,
+                    body,
+                    until: word == "until"
+                }));
+            }
+        }
+
+        if let Some(open) = self.eat_reserved("for") {
+            return self.for_clause(open).map(Some);
+        }
+
+        if let Some(open) = self.eat_reserved("case") {
+            return self.case_clause(open).map(Some);
+        }
+
+        Ok(None)
+    }
+
+    fn if_clause(&mut self, open: &Token) -> Result<CompoundCommand, ParseError> {
+        let mut branches = vec![];
+        let mut otherwise = None;
+        loop {
+            let condition = self.body(open, "then")?;
+            self.close(open, "then")?;
+            branches.push((condition, self.body(open, "fi")?));
+
+            if self.eat_reserved("elif").is_some() {
+                continue;
+            }
+
+            if self.eat_reserved("else").is_some() {
+                otherwise = Some(self.body(open, "fi")?);
+            }
+
+            self.close(open, "fi")?;
+            break;
+        }
+
+        Ok(CompoundCommand::If {
+            branches,
+            otherwise
+        })
+    }
+
+    // `for name [in word...]; do list; done`, where leaving out the words
+    // goes through the positional parameters.
+    fn for_clause(&mut self, open: &Token) -> Result<CompoundCommand, ParseError> {
+        let name = match self.peek() {
+            Some(Token::Word(word)) if word.literal().is_some_and(is_name) => word.text(),
+            Some(token) => return Err(unexpected(token)),
+            None => return Err(ParseError::incomplete("expected a variable name after `for`", open.span()))
+        };
+
+        self.idx += 1;
+        self.skip_newlines();
+        let words = self.eat_reserved("in").map(|_| self.words());
+
+        self.eat(Operator::Semicolon);
+        self.skip_newlines();
+        self.close(open, "do")?;
+        let body = self.body(open, "done")?;
+        self.close(open, "done")?;
+
+        Ok(CompoundCommand::For {
+            name,
+            words,
+            body
+        })
+    }
+
+    fn case_clause(&mut self, open: &Token) -> Result<CompoundCommand, ParseError> {
+        let word = match self.peek() {
+            Some(Token::Word(word)) => word.clone(),
+            Some(token) => return Err(unexpected(token)),
+            None => return Err(ParseError::incomplete("expected a word after `case`", open.span()))
+        };
+
+        self.idx += 1;
+        self.skip_newlines();
+        self.close(open, "in")?;
+        self.skip_newlines();
+
+        let mut items = vec![];
+        while self.eat_reserved("esac").is_none() {
+            self.eat(Operator::LeftParen);
+            let mut patterns = vec![];
+            loop {
+                match self.peek() {
+                    Some(Token::Word(word)) => patterns.push(word.clone()),
+                    Some(token) => return Err(unexpected(token)),
+                    None => return Err(ParseError::incomplete("expected a pattern", open.span()))
+                }
+
+                self.idx += 1;
+                if self.eat(Operator::Pipe).is_none() {
+                    break;
+                }
+            }
+
+            if self.eat(Operator::RightParen).is_none() {
+                return Err(match self.peek() {
+                    Some(token) => unexpected(token),
+                    None => ParseError::incomplete("expected `)` after the pattern", open.span())
+                });
+            }
+
+            let body = self.list()?;
+            let ended = self.eat(Operator::DoubleSemicolon).is_some();
+            self.skip_newlines();
+            items.push(CaseItem {
+                patterns,
+                body
+            });
+
+            // Only the last item can do without `;;`.
+            if !ended {
+                self.close(open, "esac")?;
+                break;
+            }
+        }
+
+        Ok(CompoundCommand::Case {
+            word,
+            items
+        })
+    }
+
+    // The words up to the end of the line or a `;`.
+    fn words(&mut self) -> Vec<Word> {
+        let mut words = vec![];
+        while let Some(Token::Word(word)) = self.peek() {
+            words.push(word.clone());
+            self.idx += 1;
+        }
+
+        words
+    }
+
+    // A list that has to hold at least one command, before `word`.
+    fn body(&mut self, open: &Token, word: &str) -> Result<List, ParseError> {
+        let list = self.list()?;
+        if !list.items.is_empty() {
+            return Ok(list);
+        }
+
+        Err(match self.peek() {
+            Some(token) => unexpected(token),
+            None => ParseError::incomplete(format!("`{}` without `{}`", open.text(), word), open.span())
+        })
+    }
+
+    // Expects the reserved word that goes with the `open` one.
+    fn close(&mut self, open: &Token, word: &str) -> Result<(), ParseError> {
+        if self.eat_reserved(word).is_some() {
+            return Ok(());
+        }
+
+        Err(match self.peek() {
+            Some(token) => unexpected(token),
+            None => ParseError::incomplete(format!("`{}` without `{}`", open.text(), word), open.span())
+        })
+    }
+
+    fn redirects(&mut self) -> Result<Vec<Redirect>, ParseError> {
+        let mut redirects = vec![];
+        while let Some(token @ Token::Operator(Operator::Redirect(kind, fd), _)) = self.peek() {
+            self.idx += 1;
+            redirects.push(self.redirect(token, *kind, *fd)?);
+        }
+
+        Ok(redirects)
+    }
+
+    fn simple_command(&mut self) -> Result<Option<SimpleCommand>, ParseError> {
+        let mut assignments = vec![];
+        let mut words = vec![];
+        let mut redirects = vec![];
+        loop {
+            match self.peek() {
+                // A reserved word in place of the command name ends the
+                // list it is in, like the `}` of a group.
+                Some(Token::Word(word)) if words.is_empty() && word.literal().is_some_and(is_reserved) => break,
+                // `NAME=value` only assigns in front of the command name,
+                // after it it is an ordinary argument.
+                Some(Token::Word(word)) => {
+                    match word.assignment() {
+                        Some(assignment) if words.is_empty() => assignments.push(assignment),
+                        _ => words.push(word.clone())
+                    }
+
+                    self.idx += 1;
+                },
+                Some(token @ Token::Operator(Operator::Redirect(kind, fd), _)) => {
+                    self.idx += 1;
+                    redirects.push(self.redirect(token, *kind, *fd)?);
+                },
+                _ => break
+            }
+        }
+
+        if assignments.is_empty() && words.is_empty() && redirects.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(SimpleCommand {
+            assignments,
+            words,
+            redirects
+        }))
+    }
+
+    fn redirect(&mut self, operator: &Token, kind: RedirectKind, fd: Option<u32>) -> Result<Redirect, ParseError> {
+        let target = match self.peek() {
+            Some(Token::Word(word)) => {
+                self.idx += 1;
+                word.clone()
+            },
+            Some(token) => return Err(unexpected(token)),
+            None => return Err(ParseError::new(
+                format!("expected a file name after `{}`", operator.text()),
+                operator.span()))
+        };
+
+        let fd = fd.unwrap_or(match kind {
+            RedirectKind::Input => 0,
+            _ => 1
+        });
+
+        Ok(Redirect {
+            fd,
+            kind,
+            target
+        })
+    }
+}
+
+impl Parser<'_> {
+    fn expected_command(&self, operator: &Token) -> ParseError {
+        match self.peek() {
+            Some(token) => unexpected(token),
+            None => ParseError::incomplete(format!("expected a command after `{}`", operator.text()), operator.span())
+        }
+    }
+}
+
+fn unexpected(token: &Token) -> ParseError {
+    ParseError::new(format!("unexpected `{}`", token.text()), token.span())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::lexer::Lexer;
+
+    fn parse(raw: &str) -> Result<Program, ParseError> {
+        let (tokens, error) = Lexer::new(raw).tokenize();
+        match error {
+            Some(err) => Err(err),
+            None => Parser::new(&tokens).parse()
+        }
+    }
+
+    fn program(raw: &str) -> Program {
+        parse(raw).unwrap_or_else(|err| panic!("{}: {}", raw, err))
+    }
+
+    fn simple(command: &Command) -> &SimpleCommand {
+        match command {
+            Command::Simple(command) => command,
+            other => panic!("not a simple command: {:?}", other)
+        }
+    }
+
+    fn compound(raw: &str) -> CompoundCommand {
+        match &program(raw).list.items[0].first.commands[0] {
+            Command::Compound(compound, _) => compound.clone(),
+            other => panic!("not a compound command: {:?}", other)
+        }
+    }
+
+    #[test]
+    fn lists_and_connectors() {
+        let program = program("a && b || c; d &\n\ne");
+        let items = &program.list.items;
+        assert_eq!(items.len(), 3);
+        assert_eq!(items[0].rest.iter().map(|(connector, _)| *connector).collect::<Vec<_>>(), [Connector::And, Connector::Or]);
+        assert!(!items[0].background);
+        assert!(items[1].background);
+    }
+
+    #[test]
+    fn pipelines_may_go_on_after_a_line_break() {
+        let program = program("a | b |\nc");
+        assert_eq!(program.list.items[0].first.commands.len(), 3);
+    }
+
+    #[test]
+    fn assignments_words_and_redirects() {
+        let program = program("X=1 cmd arg >out 2>&1 Y=2");
+        let command = simple(&program.list.items[0].first.commands[0]);
+        assert_eq!(command.assignments.iter().map(|assignment| assignment.name.as_str()).collect::<Vec<_>>(), ["X"]);
+        assert_eq!(command.words.iter().map(Word::text).collect::<Vec<_>>(), ["cmd", "arg", "Y=2"]);
+        assert_eq!(command.redirects.iter().map(|redirect| (redirect.fd, redirect.kind)).collect::<Vec<_>>(), [
+            (1, RedirectKind::Output),
+            (2, RedirectKind::Duplicate)
+        ]);
+    }
+
+    #[test]
+    fn reserved_words_only_count_as_command_names() {
+        let program = program("echo if then fi; 'if' x");
+        let words = |idx: usize| simple(&program.list.items[idx].first.commands[0]).words.len();
+        assert_eq!((words(0), words(1)), (4, 2));
+    }
+
+    #[test]
+    fn compound_commands() {
+        assert!(matches!(compound("if a; then b; elif c; then d; else e; fi"), CompoundCommand::If { branches, otherwise: Some(_) } if branches.len() == 2));
+        assert!(matches!(compound("while a; do b; done"), CompoundCommand::While { until: false, .. }));
+        assert!(matches!(compound("until a\ndo\nb\ndone"), CompoundCommand::While { until: true, .. }));
+        assert!(matches!(compound("for i in 1 2; do :; done"), CompoundCommand::For { words: Some(words), .. } if words.len() == 2));
+        assert!(matches!(compound("for i do :; done"), CompoundCommand::For { words: None, .. }));
+        assert!(matches!(compound("{ a; b; }"), CompoundCommand::Group(list) if list.items.len() == 2));
+
+        match compound("case $x in a|b) c;; (*) d\nesac") {
+            CompoundCommand::Case { items, .. } => {
+                assert_eq!(items.iter().map(|item| item.patterns.len()).collect::<Vec<_>>(), [2, 1]);
+            },
+            other => panic!("not a case command: {:?}", other)
+        }
+    }
+
+    #[test]
+    fn compound_commands_take_redirects() {
+        match &program("{ a; } >out | b").list.items[0].first.commands[0] {
+            Command::Compound(_, redirects) => assert_eq!(redirects.len(), 1),
+            other => panic!("not a compound command: {:?}", other)
+        }
+    }
+
+    #[test]
+    fn functions() {
+        for (raw, expected) in [("f() { a; }", "f"), ("function g { b; }", "g"), ("function h() { c; }", "h")] {
+            match &program(raw).list.items[0].first.commands[0] {
+                Command::Function(function) => assert_eq!(function.name, expected),
+                other => panic!("{}: not a function: {:?}", raw, other)
+            }
+        }
+    }
+
+    #[test]
+    fn unfinished_input_is_incomplete() {
+        for raw in ["if a; then b", "if a", "while a; do", "for i in", "for", "case x in a) b;;", "a &&", "a ||\n", "a |", "f()", "function", "{ a;"] {
+            match parse(raw) {
+                Err(err) => assert!(err.is_incomplete(), "{}: {}", raw, err),
+                Ok(_) => panic!("{}: parsed", raw)
+            }
+        }
+    }
+
+    #[test]
+    fn syntax_errors_are_not_incomplete() {
+        for raw in ["fi", "a && && b", "a | ;", "}", ";;", "if a; then fi", "f() x", "a >"] {
+            match parse(raw) {
+                Err(err) => assert!(!err.is_incomplete(), "{}: {}", raw, err),
+                Ok(_) => panic!("{}: parsed", raw)
+            }
+        }
+    }
+}
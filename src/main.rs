@@ -3,9 +3,66 @@ mod format;
 mod command;
 mod system;
 
+use std::path::Path;
+
+const USAGE: &str = "usage: sks [--norc] [-c command [name [arg...]] | file [arg...]]";
+
 fn main() {
-    let input = data::Input::new();
+    let mut args = std::env::args().skip(1).peekable();
+    let norc = args.next_if(|arg| arg == "--norc").is_some();
+
+    // `sks -c command name args` runs the command with `name` as `$0`, and
+    // `sks file args` runs the file with its path as `$0`.
+    match args.next() {
+        Some(option) if option == "-c" => {
+            let script = match args.next() {
+                Some(script) => script,
+                None => {
+                    eprintln!("sks: -c: option requires an argument\n{}", USAGE);
+                    std::process::exit(2);
+                }
+            };
+
+            let arguments = std::iter::once(args.next().unwrap_or_else(|| "sks".into()))
+                .chain(args)
+                .collect();
+
+            let mut input = data::Input::script(arguments);
+            let status = match input.run_script(&script, "sks: -c") {
+                Ok(status) => status,
+                Err(err) => {
+                    eprintln!("{}", err);
+                    input.status()
+                }
+            };
+
+            input.exit(status)
+        },
+        Some(option) if option.starts_with('-') => {
+            eprintln!("sks: {}: invalid option\n{}", option, USAGE);
+            std::process::exit(2);
+        },
+        Some(file) => {
+            let mut input = data::Input::script(std::iter::once(file.clone()).chain(args).collect());
+            let status = match input.source(Path::new(&file)) {
+                Ok(status) => status,
+                Err(err) => {
+                    eprintln!("sks: {}", err);
+                    input.status()
+                }
+            };
+
+            input.exit(status)
+        },
+        None => ()
+    }
+
+    let mut input = data::Input::new();
 
     input.welcome_message();
+    if !norc {
+        input.load_rc();
+    }
+
     for _ in input {}
-}
\ No newline at end of file
+}
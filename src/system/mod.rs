@@ -1,4 +1,6 @@
-use std::path::PathBuf;
+use std::path::{PathBuf, Path};
+#[cfg(target_family = "windows")]
+use std::process::ExitStatus;
 
 #[cfg(target_family = "windows")]
 pub fn load_executable() -> Vec<PathBuf> {
@@ -35,4 +37,299 @@ pub fn load_executable() -> Vec<PathBuf> {
     }
 
     executables
-}
\ No newline at end of file
+}
+
+#[cfg(target_family = "unix")]
+pub fn load_executable() -> Vec<PathBuf> {
+    use std::collections::HashSet;
+
+    let mut executables = vec![];
+    let mut names = HashSet::new();
+    let bin_paths = match std::env::var_os("PATH") {
+        Some(bin_paths) => bin_paths,
+        None => return executables
+    };
+
+    // Earlier PATH entries shadow later ones, so only the first file with a
+    // given name is kept, exactly like a real lookup would resolve it.
+    for path in std::env::split_paths(&bin_paths) {
+        if let Ok(d) = path.read_dir() {
+            for entry in d.flatten() {
+                let file = entry.path();
+                if is_executable(&file) && names.insert(entry.file_name()) {
+                    executables.push(file);
+                }
+            }
+        }
+    }
+
+    executables
+}
+
+#[cfg(target_family = "unix")]
+pub fn find_executable(name: &str) -> Option<PathBuf> {
+    if name.contains('/') {
+        let path = PathBuf::from(name);
+        return if is_executable(&path) { Some(path) } else { None };
+    }
+
+    let bin_paths = std::env::var_os("PATH")?;
+    std::env::split_paths(&bin_paths)
+        .map(|dir| dir.join(name))
+        .find(|path| is_executable(path))
+}
+
+#[cfg(target_family = "unix")]
+pub fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    match path.metadata() {
+        Ok(metadata) => metadata.is_file() && metadata.permissions().mode() & 0o111 != 0,
+        Err(_) => false
+    }
+}
+
+/// Exit code of a finished process.
+#[cfg(target_family = "windows")]
+pub fn exit_code(status: ExitStatus) -> i32 {
+    status.code().unwrap_or(1)
+}
+
+/// A change in the state of a child process, as reported by `waitpid`.
+#[cfg(target_family = "unix")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProcessChange {
+    Exited(i32),
+    Signaled(i32),
+    Stopped(i32),
+    Continued
+}
+
+/// Reports the next state change of `pid`, or `None` when there is none
+/// yet and `block` is off.
+#[cfg(target_family = "unix")]
+pub fn wait_process(pid: u32, block: bool) -> std::io::Result<Option<ProcessChange>> {
+    let mut flags = libc::WUNTRACED | libc::WCONTINUED;
+    if !block {
+        flags |= libc::WNOHANG;
+    }
+
+    loop {
+        let mut status = 0;
+        match unsafe { libc::waitpid(pid as libc::pid_t, &mut status, flags) } {
+            0 => return Ok(None),
+            -1 => {
+                let err = std::io::Error::last_os_error();
+                if err.kind() != std::io::ErrorKind::Interrupted {
+                    return Err(err);
+                }
+            },
+            _ => return Ok(Some(if libc::WIFEXITED(status) {
+                ProcessChange::Exited(libc::WEXITSTATUS(status))
+            } else if libc::WIFSIGNALED(status) {
+                ProcessChange::Signaled(libc::WTERMSIG(status))
+            } else if libc::WIFSTOPPED(status) {
+                ProcessChange::Stopped(libc::WSTOPSIG(status))
+            } else {
+                ProcessChange::Continued
+            }))
+        }
+    }
+}
+
+/// Blocks until `pid` has a state change to report, without collecting
+/// it, so `wait_process` still gets to see it.
+#[cfg(target_family = "unix")]
+pub fn watch_process(pid: u32) -> std::io::Result<()> {
+    loop {
+        let mut info = unsafe { std::mem::zeroed() };
+        let flags = libc::WEXITED | libc::WSTOPPED | libc::WCONTINUED | libc::WNOWAIT;
+        match unsafe { libc::waitid(libc::P_PID, pid as libc::id_t, &mut info, flags) } {
+            0 => return Ok(()),
+            _ => {
+                let err = std::io::Error::last_os_error();
+                if err.kind() != std::io::ErrorKind::Interrupted {
+                    return Err(err);
+                }
+            }
+        }
+    }
+}
+
+/// Blocks until the process behind `handle` has exited.
+#[cfg(target_family = "windows")]
+pub fn watch_process(handle: usize) -> std::io::Result<()> {
+    use winapi::um::{synchapi::WaitForSingleObject, winbase::{INFINITE, WAIT_FAILED}};
+
+    match unsafe { WaitForSingleObject(handle as _, INFINITE) } {
+        WAIT_FAILED => Err(std::io::Error::last_os_error()),
+        _ => Ok(())
+    }
+}
+
+/// A copy of the shell's own standard input, output or error. Reading from
+/// it is not buffered, and writing to it leaves the original where it is.
+#[cfg(target_family = "unix")]
+pub fn duplicate(fd: usize) -> std::io::Result<std::fs::File> {
+    use std::os::fd::AsFd;
+
+    let owned = match fd {
+        0 => std::io::stdin().as_fd().try_clone_to_owned()?,
+        1 => std::io::stdout().as_fd().try_clone_to_owned()?,
+        _ => std::io::stderr().as_fd().try_clone_to_owned()?
+    };
+
+    Ok(owned.into())
+}
+
+#[cfg(target_family = "windows")]
+pub fn duplicate(fd: usize) -> std::io::Result<std::fs::File> {
+    use std::os::windows::io::AsHandle;
+
+    let owned = match fd {
+        0 => std::io::stdin().as_handle().try_clone_to_owned()?,
+        1 => std::io::stdout().as_handle().try_clone_to_owned()?,
+        _ => std::io::stderr().as_handle().try_clone_to_owned()?
+    };
+
+    Ok(owned.into())
+}
+
+/// Sends `signal` to a process, or to a whole process group when `pid` is
+/// negative.
+#[cfg(target_family = "unix")]
+pub fn send_signal(pid: i32, signal: i32) -> std::io::Result<()> {
+    match unsafe { libc::kill(pid, signal) } {
+        0 => Ok(()),
+        _ => Err(std::io::Error::last_os_error())
+    }
+}
+
+/// Forks the shell, returning the process id of the child in the parent and
+/// `None` in the child itself. Whatever standard output still buffers is
+/// written first, or both of them would write it.
+#[cfg(target_family = "unix")]
+pub fn fork() -> std::io::Result<Option<u32>> {
+    use std::io::Write;
+
+    let _ = std::io::stdout().flush();
+    match unsafe { libc::fork() } {
+        -1 => Err(std::io::Error::last_os_error()),
+        0 => Ok(None),
+        pid => Ok(Some(pid as u32))
+    }
+}
+
+/// Puts back the signal actions a program starts with, which the shell
+/// changed for itself.
+#[cfg(target_family = "unix")]
+pub fn default_signals() {
+    for signal in [libc::SIGINT, libc::SIGQUIT, libc::SIGTSTP, libc::SIGTTIN, libc::SIGTTOU, libc::SIGPIPE, libc::SIGWINCH] {
+        unsafe {
+            libc::signal(signal, libc::SIG_DFL);
+        }
+    }
+}
+
+/// The controlling terminal of an interactive shell. Foreground jobs get
+/// the terminal handed over for as long as they run, and the shell takes
+/// it back together with its own terminal modes afterwards.
+#[cfg(target_family = "unix")]
+pub struct Terminal {
+    fd: i32,
+    pgid: libc::pid_t,
+    modes: libc::termios
+}
+
+#[cfg(target_family = "unix")]
+impl Terminal {
+    /// Returns `None` when standard input is not a terminal, in which case
+    /// there is no job control.
+    pub fn acquire() -> Option<Self> {
+        unsafe {
+            if libc::isatty(libc::STDIN_FILENO) != 1 {
+                return None;
+            }
+
+            // A duplicate survives the redirections of the children, which
+            // need it to put themselves in the foreground.
+            let fd = libc::fcntl(libc::STDIN_FILENO, libc::F_DUPFD_CLOEXEC, 10);
+            if fd < 0 {
+                return None;
+            }
+
+            let mut modes = std::mem::zeroed();
+            if libc::tcgetattr(fd, &mut modes) != 0 {
+                libc::close(fd);
+                return None;
+            }
+
+            // Taking the terminal back from a job means writing to it from
+            // a background process group.
+            libc::signal(libc::SIGTTOU, libc::SIG_IGN);
+            libc::signal(libc::SIGTTIN, libc::SIG_IGN);
+
+            Some(Self {
+                fd,
+                pgid: libc::getpgrp(),
+                modes
+            })
+        }
+    }
+
+    pub fn fd(&self) -> i32 {
+        self.fd
+    }
+
+    pub fn give(&self, pgid: u32) {
+        unsafe {
+            libc::tcsetpgrp(self.fd, pgid as libc::pid_t);
+        }
+    }
+
+    pub fn reclaim(&self) {
+        unsafe {
+            libc::tcsetpgrp(self.fd, self.pgid);
+            libc::tcsetattr(self.fd, libc::TCSADRAIN, &self.modes);
+        }
+    }
+}
+
+#[cfg(target_family = "unix")]
+pub const SIGNALS: [(&str, i32); 20] = [
+    ("HUP", libc::SIGHUP),
+    ("INT", libc::SIGINT),
+    ("QUIT", libc::SIGQUIT),
+    ("ILL", libc::SIGILL),
+    ("TRAP", libc::SIGTRAP),
+    ("ABRT", libc::SIGABRT),
+    ("BUS", libc::SIGBUS),
+    ("FPE", libc::SIGFPE),
+    ("KILL", libc::SIGKILL),
+    ("USR1", libc::SIGUSR1),
+    ("SEGV", libc::SIGSEGV),
+    ("USR2", libc::SIGUSR2),
+    ("PIPE", libc::SIGPIPE),
+    ("ALRM", libc::SIGALRM),
+    ("TERM", libc::SIGTERM),
+    ("CHLD", libc::SIGCHLD),
+    ("CONT", libc::SIGCONT),
+    ("STOP", libc::SIGSTOP),
+    ("TSTP", libc::SIGTSTP),
+    ("WINCH", libc::SIGWINCH)
+];
+
+/// Accepts a signal number or a name with or without the `SIG` prefix.
+#[cfg(target_family = "unix")]
+pub fn signal_number(signal: &str) -> Option<i32> {
+    if let Ok(number) = signal.parse() {
+        return SIGNALS.iter().any(|&(_, known)| known == number).then_some(number);
+    }
+
+    let name = signal.to_ascii_uppercase();
+    let name = name.strip_prefix("SIG").unwrap_or(&name);
+    SIGNALS
+        .iter()
+        .find(|&&(known, _)| known == name)
+        .map(|&(_, number)| number)
+}